@@ -1,7 +1,176 @@
+use std::fmt;
+
+use super::reqwest_compat as reqwest;
+
+
 error_chain! {
     foreign_links {
+        IoError(::std::io::Error) #[doc="I/O error."];
+        Base64Error(::base64::DecodeError) #[doc="Base64 decoding error."];
         ReqwestError(::reqwest::Error) #[doc="Reqwest error."];
         JsonError(::serde_json::Error) #[doc="JSON error."];
         UrlParseError(::url::ParseError) #[doc="URL parsing error."];
     }
+
+    errors {
+        /// Error response from the Qiniu API.
+        QiniuApiError(e: ApiError) {
+            description("Qiniu API error")
+            display("Qiniu API error: {}", e)
+        }
+
+        /// The request kept failing after being attempted the given number
+        /// of times, with the last error.
+        RetriesExhausted(attempts: u32, last: Box<Error>) {
+            description("request failed after retries")
+            display("failed after {} attempts: {}", attempts, last)
+        }
+    }
+}
+
+
+impl Error {
+    /// Returns the API error details, if this is an error response from the
+    /// Qiniu API, possibly after retries.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self.kind() {
+            &ErrorKind::QiniuApiError(ref e) => Some(e),
+            &ErrorKind::RetriesExhausted(_, ref last) => last.api_error(),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of times the request was attempted, if it was
+    /// retried.
+    pub fn attempts(&self) -> Option<u32> {
+        match self.kind() {
+            &ErrorKind::RetriesExhausted(attempts, _) => Some(attempts),
+            _ => None,
+        }
+    }
+}
+
+
+/// Details of a non-2xx response from the Qiniu API.
+///
+/// See [the error code reference][codes] for the meaning of status codes.
+///
+/// [codes]: https://developer.qiniu.com/kodo/api/3928/error-responses
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ApiError {
+    /// HTTP status code.
+    pub status: u16,
+    /// The `error` field of the response body, if any.
+    pub error: Option<String>,
+    /// Value of the `X-Reqid` header, useful when contacting support.
+    pub reqid: Option<String>,
+    /// Value of the `X-Log` header.
+    pub log: Option<String>,
+}
+
+
+/// Body of API error responses.
+#[derive(Deserialize)]
+pub(crate) struct ApiErrorBody {
+    pub error: Option<String>,
+}
+
+
+fn header_str(headers: &reqwest::header::Headers, name: &str) -> Option<String> {
+    headers
+        .get_raw(name)
+        .and_then(|raw| raw.one())
+        .map(|x| String::from_utf8_lossy(x).into_owned())
+}
+
+
+impl ApiError {
+    pub(crate) fn new(status: u16, headers: &reqwest::header::Headers, error: Option<String>) -> ApiError {
+        ApiError {
+            status: status,
+            error: error,
+            reqid: header_str(headers, "X-Reqid"),
+            log: header_str(headers, "X-Log"),
+        }
+    }
+
+    /// Returns if the upload token or access token is invalid (401).
+    pub fn is_bad_token(&self) -> bool {
+        self.status == 401
+    }
+
+    /// Returns if the requested object does not exist (612).
+    pub fn is_not_found(&self) -> bool {
+        self.status == 612
+    }
+
+    /// Returns if the target object already exists (614).
+    pub fn is_already_exists(&self) -> bool {
+        self.status == 614
+    }
+
+    /// Returns if the bucket does not exist (631).
+    pub fn is_bucket_not_found(&self) -> bool {
+        self.status == 631
+    }
+
+    /// Returns if the request was rejected due to quota or rate limits (573).
+    pub fn is_over_quota(&self) -> bool {
+        self.status == 573
+    }
+
+    /// Returns if the error is on the server side (5xx).
+    pub fn is_server_error(&self) -> bool {
+        self.status >= 500 && self.status < 600
+    }
+
+    /// Returns if another host of the same service may succeed, i.e. on
+    /// server errors including 571 and 573 (rate limited), but not on 579
+    /// where the upload succeeded and only the callback failed.
+    pub fn is_host_failure(&self) -> bool {
+        self.is_server_error() && self.status != 579
+    }
+}
+
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "status {}", self.status)?;
+        if let Some(ref error) = self.error {
+            write!(f, ": {}", error)?;
+        }
+        if let Some(ref reqid) = self.reqid {
+            write!(f, " (reqid {})", reqid)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_api_error() {
+        let mut headers = reqwest::header::Headers::new();
+        headers.set_raw("X-Reqid", "MOQAAMIr3tnZXvwU");
+        let e = ApiError::new(612, &headers, Some("no such file or directory".to_owned()));
+
+        assert!(e.is_not_found());
+        assert!(!e.is_server_error());
+        assert!(!e.is_host_failure());
+        assert!(ApiError::new(573, &headers, None).is_host_failure());
+        assert!(!ApiError::new(579, &headers, None).is_host_failure());
+        assert_eq!(e.log, None);
+        assert_eq!(
+            format!("{}", e),
+            "status 612: no such file or directory (reqid MOQAAMIr3tnZXvwU)"
+        );
+
+        let e: Error = ErrorKind::QiniuApiError(e).into();
+        assert_eq!(e.api_error().map(|e| e.status), Some(612));
+    }
 }
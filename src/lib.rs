@@ -3,9 +3,11 @@
 
 extern crate base64;
 extern crate bytes;
+extern crate crc;
 #[macro_use]
 extern crate error_chain;
 extern crate futures;
+extern crate hyper;
 extern crate reqwest;
 extern crate ring;
 extern crate serde;
@@ -17,9 +19,13 @@ extern crate url;
 extern crate tokio_core;
 
 pub mod errors;
+mod multipart;
 pub mod provider;
+pub mod qetag;
+pub mod region;
 mod request;
 mod reqwest_compat;
-mod sign;
+pub mod retry;
+pub mod sign;
 
 pub mod storage;
@@ -0,0 +1,106 @@
+//! Minimal `multipart/form-data` body encoder.
+//!
+//! The async flavor of reqwest does not support multipart bodies, so forms are
+//! serialized by hand into a single buffer for both flavors.
+
+use bytes;
+use ring::rand::{self, SecureRandom};
+
+use super::errors::*;
+
+
+pub(crate) struct MultipartForm {
+    boundary: String,
+    buf: Vec<u8>,
+}
+
+
+impl MultipartForm {
+    pub(crate) fn new() -> Result<MultipartForm> {
+        let mut nonce = [0u8; 16];
+        rand::SystemRandom::new().fill(&mut nonce).map_err(
+            |_| Error::from("failed to generate multipart boundary"),
+        )?;
+
+        let mut boundary = String::from("------------------------qiniu");
+        for b in nonce.iter() {
+            boundary.push_str(&format!("{:02x}", b));
+        }
+
+        Ok(MultipartForm::with_boundary(boundary))
+    }
+
+    fn with_boundary(boundary: String) -> MultipartForm {
+        MultipartForm {
+            boundary: boundary,
+            buf: Vec::new(),
+        }
+    }
+
+    fn begin_part(&mut self, name: &str, file_name: Option<&str>) {
+        self.buf.extend_from_slice(b"--");
+        self.buf.extend_from_slice(self.boundary.as_bytes());
+        self.buf.extend_from_slice(b"\r\nContent-Disposition: form-data; name=\"");
+        self.buf.extend_from_slice(escape_quoted(name).as_bytes());
+        self.buf.push(b'"');
+        if let Some(file_name) = file_name {
+            self.buf.extend_from_slice(b"; filename=\"");
+            self.buf.extend_from_slice(escape_quoted(file_name).as_bytes());
+            self.buf.push(b'"');
+        }
+        self.buf.extend_from_slice(b"\r\n");
+    }
+
+    pub(crate) fn add_text(&mut self, name: &str, value: &str) {
+        self.begin_part(name, None);
+        self.buf.extend_from_slice(b"\r\n");
+        self.buf.extend_from_slice(value.as_bytes());
+        self.buf.extend_from_slice(b"\r\n");
+    }
+
+    pub(crate) fn add_file(&mut self, name: &str, file_name: &str, content_type: &str, data: &[u8]) {
+        self.begin_part(name, Some(file_name));
+        self.buf.extend_from_slice(b"Content-Type: ");
+        self.buf.extend_from_slice(content_type.as_bytes());
+        self.buf.extend_from_slice(b"\r\n\r\n");
+        self.buf.extend_from_slice(data);
+        self.buf.extend_from_slice(b"\r\n");
+    }
+
+    /// Finish the form, returning the `Content-Type` header value and the
+    /// encoded body.
+    pub(crate) fn finish(mut self) -> (String, bytes::Bytes) {
+        self.buf.extend_from_slice(b"--");
+        self.buf.extend_from_slice(self.boundary.as_bytes());
+        self.buf.extend_from_slice(b"--\r\n");
+
+        let content_type = format!("multipart/form-data; boundary={}", self.boundary);
+        (content_type, self.buf.into())
+    }
+}
+
+
+fn escape_quoted(x: &str) -> String {
+    x.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_multipart_form() {
+        let mut form = MultipartForm::with_boundary("xyz".to_owned());
+        form.add_text("token", "a:b:c");
+        form.add_file("file", "a\"b.txt", "text/plain", b"hello");
+        let (ct, body) = form.finish();
+
+        assert_eq!(ct, "multipart/form-data; boundary=xyz");
+        assert_eq!(
+            &body[..],
+            &b"--xyz\r\nContent-Disposition: form-data; name=\"token\"\r\n\r\na:b:c\r\n--xyz\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a\\\"b.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n--xyz--\r\n"[..]
+        );
+    }
+}
@@ -1,54 +1,355 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time;
+
+#[cfg(feature = "async-api")]
+use futures::future;
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+#[cfg(feature = "async-api")]
+use futures::sync::oneshot;
 #[cfg(feature = "async-api")]
 use tokio_core::reactor;
 
+use hyper;
 use url;
 
 use super::errors::*;
+use super::region;
+use super::retry;
 use super::sign;
 use super::request;
 use super::reqwest_compat as reqwest;
 
 
 pub struct QiniuClient {
-    signer: sign::QiniuSigner,
+    signer: Arc<sign::QiniuSigner>,
     client: reqwest::Client,
+    #[cfg(feature = "async-api")]
+    handle: Option<reactor::Handle>,
 
     hosts: QiniuHosts,
+    region_cache: Arc<region::RegionCache>,
+    freezer: Arc<HostFreezer>,
+    retry: retry::RetryPolicy,
 }
 
+
+/// Services of a region.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Service {
+    /// Upload.
+    Up,
+    /// Source download and fetching.
+    Io,
+    /// Resource management.
+    Rs,
+    /// Resource listing.
+    Rsf,
+    /// Bucket domains and other APIs.
+    Api,
+    /// Bucket configuration and region queries.
+    Uc,
+}
+
+
+/// Service hosts of a region.
+///
+/// Every service has an ordered list of endpoints. Requests go to the first
+/// one, and fail over to the others on connection errors or server errors.
+/// Requests that are not idempotent only fail over if they could not
+/// connect.
+///
+/// Use [QiniuHostsBuilder] to point clients at private deployments or test
+/// servers.
+///
+/// [QiniuHostsBuilder]: ./struct.QiniuHostsBuilder.html
+#[derive(Clone, PartialEq, Eq, Debug)]
 pub struct QiniuHosts {
-    rs: url::Url,
-    rsf: url::Url,
-    api: url::Url,
+    up: Vec<url::Url>,
+    io: Vec<url::Url>,
+    rs: Vec<url::Url>,
+    rsf: Vec<url::Url>,
+    api: Vec<url::Url>,
+    uc: Vec<url::Url>,
 }
 
 
 impl Default for QiniuHosts {
+    /// Hosts of the `z0` region.
     fn default() -> QiniuHosts {
-        QiniuHosts {
-            rs: "https://rs.qiniu.com".parse().unwrap(),
-            rsf: "https://rsf.qiniu.com".parse().unwrap(),
-            api: "https://api.qiniu.com".parse().unwrap(),
-        }
+        region::Region::Z0.hosts()
     }
 }
 
 
 impl QiniuHosts {
+    /// Returns the endpoints of the service, in order of preference. The
+    /// list is never empty.
+    pub fn endpoints(&self, service: Service) -> &[url::Url] {
+        match service {
+            Service::Up => &self.up,
+            Service::Io => &self.io,
+            Service::Rs => &self.rs,
+            Service::Rsf => &self.rsf,
+            Service::Api => &self.api,
+            Service::Uc => &self.uc,
+        }
+    }
+
     pub fn rs(&self) -> &url::Url {
-        &self.rs
+        &self.rs[0]
     }
 
     pub fn rsf(&self) -> &url::Url {
-        &self.rsf
+        &self.rsf[0]
     }
 
     pub fn api(&self) -> &url::Url {
-        &self.api
+        &self.api[0]
+    }
+
+    pub fn up(&self) -> &url::Url {
+        &self.up[0]
+    }
+
+    pub fn io(&self) -> &url::Url {
+        &self.io[0]
+    }
+
+    pub fn uc(&self) -> &url::Url {
+        &self.uc[0]
+    }
+}
+
+
+/// Builder of [QiniuHosts].
+///
+/// Hosts are given as domains with optional ports, e.g. `127.0.0.1:9000`.
+///
+/// ```ignore
+/// let hosts = QiniuHostsBuilder::new(Region::Z0)
+///     .https(false)
+///     .all("127.0.0.1:9000")
+///     .build()?;
+/// ```
+///
+/// [QiniuHosts]: ./struct.QiniuHosts.html
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QiniuHostsBuilder {
+    https: bool,
+    up: Vec<String>,
+    io: Vec<String>,
+    rs: Vec<String>,
+    rsf: Vec<String>,
+    api: Vec<String>,
+    uc: Vec<String>,
+}
+
+
+impl QiniuHostsBuilder {
+    /// Begin with the HTTPS hosts of the region.
+    pub fn new(region: region::Region) -> Self {
+        let domains = |service| -> Vec<String> {
+            region.domains(service).iter().map(|&x| x.to_owned()).collect()
+        };
+
+        QiniuHostsBuilder {
+            https: true,
+            up: domains(Service::Up),
+            io: domains(Service::Io),
+            rs: domains(Service::Rs),
+            rsf: domains(Service::Rsf),
+            api: domains(Service::Api),
+            uc: domains(Service::Uc),
+        }
+    }
+
+    /// Use HTTPS (the default) or plain HTTP for all hosts.
+    pub fn https(mut self, https: bool) -> Self {
+        self.https = https;
+        self
+    }
+
+    /// Use the same single host for all services, e.g. a mock server.
+    pub fn all(self, host: &str) -> Self {
+        self.up(host).io(host).rs(host).rsf(host).api(host).uc(host)
+    }
+
+    /// Set the hosts of the service, in order of preference.
+    pub fn endpoints<S: AsRef<str>>(mut self, service: Service, hosts: &[S]) -> Self {
+        let hosts = hosts.iter().map(|x| x.as_ref().to_owned()).collect();
+        match service {
+            Service::Up => self.up = hosts,
+            Service::Io => self.io = hosts,
+            Service::Rs => self.rs = hosts,
+            Service::Rsf => self.rsf = hosts,
+            Service::Api => self.api = hosts,
+            Service::Uc => self.uc = hosts,
+        }
+        self
+    }
+
+    pub fn up(self, host: &str) -> Self {
+        self.endpoints(Service::Up, &[host])
+    }
+
+    pub fn io(self, host: &str) -> Self {
+        self.endpoints(Service::Io, &[host])
+    }
+
+    pub fn rs(self, host: &str) -> Self {
+        self.endpoints(Service::Rs, &[host])
+    }
+
+    pub fn rsf(self, host: &str) -> Self {
+        self.endpoints(Service::Rsf, &[host])
+    }
+
+    pub fn api(self, host: &str) -> Self {
+        self.endpoints(Service::Api, &[host])
+    }
+
+    pub fn uc(self, host: &str) -> Self {
+        self.endpoints(Service::Uc, &[host])
+    }
+
+    fn host_urls(&self, hosts: &[String]) -> Result<Vec<url::Url>> {
+        if hosts.is_empty() {
+            bail!("no hosts given for service");
+        }
+
+        hosts.iter().map(|host| self.host_url(host)).collect()
+    }
+
+    fn host_url(&self, host: &str) -> Result<url::Url> {
+        let mut tmp = String::from(if self.https { "https://" } else { "http://" });
+        tmp.push_str(host);
+        let url = url::Url::parse(&tmp)?;
+
+        // anything beyond the authority would be silently dropped when
+        // joining API paths
+        if url.path() != "/" || url.query().is_some() || url.fragment().is_some() ||
+            !url.username().is_empty() || url.password().is_some()
+        {
+            bail!("invalid host: {}", host);
+        }
+
+        Ok(url)
+    }
+
+    /// Return the hosts, or an error if any of them is invalid or a service
+    /// has no hosts.
+    pub fn build(self) -> Result<QiniuHosts> {
+        Ok(QiniuHosts {
+            up: self.host_urls(&self.up)?,
+            io: self.host_urls(&self.io)?,
+            rs: self.host_urls(&self.rs)?,
+            rsf: self.host_urls(&self.rsf)?,
+            api: self.host_urls(&self.api)?,
+            uc: self.host_urls(&self.uc)?,
+        })
+    }
+}
+
+
+/// Default duration hosts are skipped for after failures.
+pub const DEFAULT_HOST_FREEZE_SECS: u64 = 600;
+
+
+/// Hosts temporarily skipped after failures.
+struct HostFreezer {
+    duration: time::Duration,
+    frozen: Mutex<HashMap<String, time::Instant>>,
+}
+
+
+impl HostFreezer {
+    fn new(duration: time::Duration) -> HostFreezer {
+        HostFreezer {
+            duration: duration,
+            frozen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn key(uri: &url::Url) -> String {
+        uri.origin().ascii_serialization()
+    }
+
+    fn freeze(&self, uri: &url::Url) {
+        if let Ok(mut frozen) = self.frozen.lock() {
+            frozen.insert(HostFreezer::key(uri), time::Instant::now() + self.duration);
+        }
+    }
+
+    fn is_frozen(&self, uri: &url::Url) -> bool {
+        match self.frozen.lock() {
+            Ok(frozen) => {
+                frozen.get(&HostFreezer::key(uri)).map_or(
+                    false,
+                    |&until| time::Instant::now() < until,
+                )
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Order the URIs to try, moving those on frozen hosts to the end. If
+    /// all hosts are frozen, they are still tried in the original order.
+    fn order(&self, uris: Vec<url::Url>) -> Vec<url::Url> {
+        let (mut ok, frozen): (Vec<_>, Vec<_>) = uris.into_iter().partition(|u| !self.is_frozen(u));
+        ok.extend(frozen);
+        ok
+    }
+}
+
+
+/// Returns if the host failed, i.e. could not be reached or responded with
+/// a server error, and should be frozen.
+fn is_host_failure(e: &Error) -> bool {
+    match e.kind() {
+        &ErrorKind::ReqwestError(_) => true,
+        &ErrorKind::QiniuApiError(ref e) => e.is_host_failure(),
+        _ => false,
+    }
+}
+
+
+/// Returns if the request should be sent to the next host after the error.
+///
+/// Requests that are not idempotent are only sent again if they never
+/// reached the host, as they may be applied twice otherwise.
+fn should_fail_over(e: &Error, idempotent: bool) -> bool {
+    match e.kind() {
+        &ErrorKind::ReqwestError(ref e) => idempotent || is_connect_error(e),
+        &ErrorKind::QiniuApiError(ref e) => idempotent && e.is_host_failure(),
+        _ => false,
     }
 }
 
 
+/// Returns if the connection to the host could not be established, so the
+/// request was never sent.
+fn is_connect_error(e: &::reqwest::Error) -> bool {
+    let inner = match e.get_ref() {
+        Some(inner) => inner,
+        None => return false,
+    };
+    let io = match inner.downcast_ref::<hyper::Error>() {
+        Some(&hyper::Error::Io(ref io)) => Some(io),
+        _ => inner.downcast_ref::<io::Error>(),
+    };
+
+    io.map_or(false, |io| match io.kind() {
+        io::ErrorKind::ConnectionRefused |
+        io::ErrorKind::AddrNotAvailable => true,
+        _ => false,
+    })
+}
+
+
 impl QiniuClient {
     #[cfg(feature = "async-api")]
     pub fn new<AK, SK>(handle: &reactor::Handle, ak: AK, sk: SK) -> QiniuClient
@@ -57,7 +358,7 @@ impl QiniuClient {
         SK: AsRef<str>,
     {
         let client = reqwest::Client::new(handle);
-        QiniuClient::new_with_client(client, ak.as_ref(), sk.as_ref())
+        QiniuClient::new_with_client(client, ak.as_ref(), sk.as_ref()).with_handle(handle)
     }
 
     #[cfg(feature = "sync-api")]
@@ -70,6 +371,7 @@ impl QiniuClient {
         QiniuClient::new_with_client(client, ak.as_ref(), sk.as_ref())
     }
 
+    /// Create a client sending requests with the reqwest client.
     pub fn new_with_client<AK, SK>(client: reqwest::Client, ak: AK, sk: SK) -> QiniuClient
     where
         AK: AsRef<str>,
@@ -78,36 +380,250 @@ impl QiniuClient {
         let signer = sign::QiniuSigner::new(ak, sk);
 
         QiniuClient {
-            signer: signer,
+            signer: Arc::new(signer),
             client: client,
+            #[cfg(feature = "async-api")]
+            handle: None,
             hosts: QiniuHosts::default(),
+            region_cache: Arc::new(region::RegionCache::default()),
+            freezer: Arc::new(HostFreezer::new(
+                time::Duration::from_secs(DEFAULT_HOST_FREEZE_SECS),
+            )),
+            retry: retry::RetryPolicy::default(),
         }
     }
 
-    pub(crate) fn signer(&self) -> &sign::QiniuSigner {
-        &self.signer
+    /// Time delays between retries with the reactor, instead of sleeping on
+    /// a helper thread.
+    #[cfg(feature = "async-api")]
+    pub fn with_handle(mut self, handle: &reactor::Handle) -> Self {
+        self.handle = Some(handle.clone());
+        self
+    }
+
+    /// Use the given hosts instead of the defaults of the `z0` region.
+    pub fn with_hosts(mut self, hosts: QiniuHosts) -> Self {
+        self.hosts = hosts;
+        self
+    }
+
+    /// Set the duration hosts are skipped for after failures, defaulting to
+    /// [DEFAULT_HOST_FREEZE_SECS].
+    ///
+    /// [DEFAULT_HOST_FREEZE_SECS]: ./constant.DEFAULT_HOST_FREEZE_SECS.html
+    pub fn with_host_freeze_duration(mut self, duration: time::Duration) -> Self {
+        self.freezer = Arc::new(HostFreezer::new(duration));
+        self
+    }
+
+    /// Set the policy of retrying failed requests, defaulting to
+    /// `RetryPolicy::default()`.
+    pub fn with_retry_policy(mut self, retry: retry::RetryPolicy) -> Self {
+        self.retry = retry;
+        self
     }
 
-    pub(crate) fn reqwest_client(&self) -> &reqwest::Client {
-        &self.client
+    pub(crate) fn signer(&self) -> &sign::QiniuSigner {
+        &self.signer
     }
 
+    /// Execute the request, retrying according to the retry policy.
     #[cfg(feature = "async-api")]
     pub(crate) fn execute(
         &self,
         req: request::QiniuRequest,
-    ) -> Result<impl ::futures::Future<Item = reqwest::Response, Error = reqwest::Error>> {
-        let ll_req = req.into_lowlevel(self)?;
+    ) -> Result<impl Future<Item = reqwest::Response, Error = Error>> {
+        let req = Arc::new(req);
+        let idempotent = req.is_idempotent();
+        let client = self.client.clone();
+        let signer = self.signer.clone();
+        let freezer = self.freezer.clone();
+        let policy = self.retry.clone();
+        let handle = self.handle.clone();
+
+        let x = future::loop_fn(1, move |attempts| {
+            let policy = policy.clone();
+            let handle = handle.clone();
+            let x = send(&client, &signer, &freezer, req.clone());
 
-        Ok(self.client.execute(ll_req))
+            x.then(move |resp| match resp {
+                Ok(resp) => future::Either::A(future::ok(future::Loop::Break(resp))),
+                Err(e) => {
+                    if !policy.should_retry(&e, idempotent, attempts) {
+                        return future::Either::A(future::err(policy.give_up(e, attempts)));
+                    }
+
+                    let x = sleep(handle, policy.delay(attempts));
+                    future::Either::B(x.map(move |_| future::Loop::Continue(attempts + 1)))
+                }
+            })
+        });
+
+        Ok(x)
     }
 
+    /// Execute the request, retrying according to the retry policy.
     #[cfg(feature = "sync-api")]
     pub(crate) fn execute(&self, req: request::QiniuRequest) -> Result<reqwest::Response> {
-        let ll_req = req.into_lowlevel(self)?;
+        let idempotent = req.is_idempotent();
+
+        let mut attempts = 1;
+        loop {
+            match send(&self.client, &self.signer, &self.freezer, &req) {
+                Ok(resp) => return Ok(resp),
+                Err(e) => {
+                    if !self.retry.should_retry(&e, idempotent, attempts) {
+                        return Err(self.retry.give_up(e, attempts));
+                    }
+
+                    thread::sleep(self.retry.delay(attempts));
+                    attempts += 1;
+                }
+            }
+        }
+    }
+}
+
+
+/// Future completing after the delay, timed by the reactor if there is one
+/// and by a helper thread otherwise.
+#[cfg(feature = "async-api")]
+fn sleep(handle: Option<reactor::Handle>, delay: time::Duration) -> impl Future<Item = (), Error = Error> {
+    match handle {
+        Some(handle) => {
+            let x = future::result(reactor::Timeout::new(delay, &handle)).flatten();
+            future::Either::A(x.map_err(Error::from))
+        }
+        None => {
+            let (tx, rx) = oneshot::channel();
+            thread::spawn(move || {
+                thread::sleep(delay);
+                let _ = tx.send(());
+            });
+            future::Either::B(rx.map_err(|_| Error::from("retry delay timer was dropped")))
+        }
+    }
+}
+
+
+/// Send the request to the endpoints in turn until one succeeds or the error
+/// should not fail over.
+#[cfg(feature = "async-api")]
+fn send(
+    client: &reqwest::Client,
+    signer: &Arc<sign::QiniuSigner>,
+    freezer: &Arc<HostFreezer>,
+    req: Arc<request::QiniuRequest>,
+) -> impl Future<Item = reqwest::Response, Error = Error> {
+    let uris = freezer.order(req.uris());
+    let idempotent = req.is_idempotent();
+    let client = client.clone();
+    let signer = signer.clone();
+    let freezer = freezer.clone();
+
+    future::loop_fn(uris.into_iter(), move |mut uris| {
+        // there is always at least the URI of the request itself
+        let uri = uris.next().unwrap();
+        let freezer = freezer.clone();
+
+        // requests are signed per host, as the signature may cover the host,
+        // and only built when sent
+        let ll_req = match req.to_lowlevel(&client, &signer, &uri) {
+            Ok(x) => x,
+            Err(e) => return future::Either::A(future::err(e)),
+        };
+
+        let x = client.execute(ll_req).map_err(Error::from).and_then(check_response).then(
+            move |resp| match resp {
+                Ok(resp) => Ok(future::Loop::Break(resp)),
+                Err(e) => {
+                    if is_host_failure(&e) {
+                        freezer.freeze(&uri);
+                    }
+                    if !should_fail_over(&e, idempotent) || uris.as_slice().is_empty() {
+                        return Err(e);
+                    }
+                    Ok(future::Loop::Continue(uris))
+                }
+            },
+        );
+
+        future::Either::B(x)
+    })
+}
+
+
+/// Send the request to the endpoints in turn until one succeeds or the error
+/// should not fail over.
+#[cfg(feature = "sync-api")]
+fn send(
+    client: &reqwest::Client,
+    signer: &sign::QiniuSigner,
+    freezer: &HostFreezer,
+    req: &request::QiniuRequest,
+) -> Result<reqwest::Response> {
+    let idempotent = req.is_idempotent();
+    let mut last_error = None;
+
+    for uri in freezer.order(req.uris()) {
+        // requests are signed per host, as the signature may cover the host,
+        // and only built when sent
+        let ll_req = req.to_lowlevel(client, signer, &uri)?;
+        let resp = client.execute(ll_req).map_err(Error::from).and_then(
+            check_response,
+        );
+
+        match resp {
+            Ok(resp) => return Ok(resp),
+            Err(e) => {
+                if is_host_failure(&e) {
+                    freezer.freeze(&uri);
+                }
+                let fail_over = should_fail_over(&e, idempotent);
+                last_error = Some(e);
+                if !fail_over {
+                    break;
+                }
+            }
+        }
+    }
+
+    Err(last_error.unwrap_or_else(
+        || "no endpoint to send the request to".into(),
+    ))
+}
+
+
+/// Turn non-2xx responses into `QiniuApiError`s.
+#[cfg(feature = "async-api")]
+fn check_response(mut resp: reqwest::Response) -> impl Future<Item = reqwest::Response, Error = Error> {
+    if resp.status().is_success() {
+        return future::Either::A(future::ok(resp));
+    }
+
+    let status = resp.status().as_u16();
+    let headers = resp.headers().clone();
+    let x = resp.json().then(move |body: ::std::result::Result<ApiErrorBody, reqwest::Error>| -> Result<reqwest::Response> {
+        let error = body.ok().and_then(|b| b.error);
+        Err(ErrorKind::QiniuApiError(ApiError::new(status, &headers, error)).into())
+    });
+
+    future::Either::B(x)
+}
 
-        Ok(self.client.execute(ll_req)?)
+
+/// Turn non-2xx responses into `QiniuApiError`s.
+#[cfg(feature = "sync-api")]
+fn check_response(mut resp: reqwest::Response) -> Result<reqwest::Response> {
+    if resp.status().is_success() {
+        return Ok(resp);
     }
+
+    let status = resp.status().as_u16();
+    let error = resp.json::<ApiErrorBody>().ok().and_then(|b| b.error);
+    Err(
+        ErrorKind::QiniuApiError(ApiError::new(status, resp.headers(), error)).into(),
+    )
 }
 
 
@@ -115,4 +631,121 @@ impl QiniuClient {
     pub fn hosts(&self) -> &QiniuHosts {
         &self.hosts
     }
+
+    /// Discover the hosts of the region the bucket is in.
+    ///
+    /// Results are cached in memory for the TTL given by the server.
+    #[cfg(feature = "async-api")]
+    pub fn bucket_hosts(&self, bucket: &str) -> impl Future<Item = QiniuHosts, Error = Error> {
+        if let Some(hosts) = self.region_cache.get(bucket) {
+            return future::Either::A(future::ok(hosts));
+        }
+
+        let cache = self.region_cache.clone();
+        let uc = self.hosts.uc().clone();
+        let bucket = bucket.to_owned();
+        let req = region::req_query(self, &bucket).and_then(|req| self.execute(req));
+        let x = future::result(req).and_then(|x| {
+            x.and_then(|mut x| x.json().map_err(|e| e.into()))
+        });
+        let x = x.and_then(move |resp: region::RegionQueryResponse| {
+            let (hosts, ttl) = resp.into_hosts(&uc)?;
+            cache.insert(bucket, hosts.clone(), ttl);
+            Ok(hosts)
+        });
+
+        future::Either::B(x)
+    }
+
+    /// Discover the hosts of the region the bucket is in.
+    ///
+    /// Results are cached in memory for the TTL given by the server.
+    #[cfg(feature = "sync-api")]
+    pub fn bucket_hosts(&self, bucket: &str) -> Result<QiniuHosts> {
+        if let Some(hosts) = self.region_cache.get(bucket) {
+            return Ok(hosts);
+        }
+
+        let req = region::req_query(self, bucket)?;
+        let resp: region::RegionQueryResponse = self.execute(req)?.json()?;
+        let (hosts, ttl) = resp.into_hosts(self.hosts.uc())?;
+        self.region_cache.insert(bucket.to_owned(), hosts.clone(), ttl);
+
+        Ok(hosts)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_hosts_builder() {
+        assert_eq!(
+            QiniuHostsBuilder::new(region::Region::Z0).build().unwrap(),
+            QiniuHosts::default()
+        );
+
+        let hosts = QiniuHostsBuilder::new(region::Region::Z1)
+            .https(false)
+            .all("127.0.0.1:9000")
+            .up("up.example.com")
+            .build()
+            .unwrap();
+        assert_eq!(hosts.up().as_str(), "http://up.example.com/");
+        assert_eq!(hosts.rs().as_str(), "http://127.0.0.1:9000/");
+        assert_eq!(hosts.uc().as_str(), "http://127.0.0.1:9000/");
+        assert_eq!(hosts.rs().join("batch").unwrap().as_str(), "http://127.0.0.1:9000/batch");
+
+        let hosts = QiniuHostsBuilder::new(region::Region::Z0)
+            .endpoints(Service::Rs, &["rs1.example.com", "rs2.example.com:8080"])
+            .build()
+            .unwrap();
+        assert_eq!(hosts.rs().as_str(), "https://rs1.example.com/");
+        assert_eq!(hosts.endpoints(Service::Rs)[1].as_str(), "https://rs2.example.com:8080/");
+        let empty: &[&str] = &[];
+        assert!(QiniuHostsBuilder::new(region::Region::Z0).endpoints(Service::Up, empty).build().is_err());
+
+        for host in &["", "https://rs.qiniu.com", "rs.qiniu.com/prefix", "rs.qiniu.com?a", "u@rs.qiniu.com"] {
+            assert!(QiniuHostsBuilder::new(region::Region::Z0).rs(host).build().is_err());
+        }
+    }
+
+    #[test]
+    fn test_host_freezer() {
+        let uris = ["https://a.com/x", "https://b.com/x", "https://c.com/x"]
+            .iter()
+            .map(|x| url::Url::parse(x).unwrap())
+            .collect::<Vec<_>>();
+
+        let freezer = HostFreezer::new(time::Duration::from_secs(60));
+        assert_eq!(freezer.order(uris.clone()), uris);
+
+        freezer.freeze(&url::Url::parse("https://a.com/other").unwrap());
+        assert_eq!(
+            freezer.order(uris.clone()),
+            vec![uris[1].clone(), uris[2].clone(), uris[0].clone()]
+        );
+
+        let freezer = HostFreezer::new(time::Duration::from_secs(0));
+        freezer.freeze(&uris[0]);
+        assert_eq!(freezer.order(uris.clone()), uris);
+    }
+
+    #[test]
+    fn test_should_fail_over() {
+        let headers = reqwest::header::Headers::new();
+        let e503: Error = ErrorKind::QiniuApiError(ApiError::new(503, &headers, None)).into();
+        let e579: Error = ErrorKind::QiniuApiError(ApiError::new(579, &headers, None)).into();
+        let e612: Error = ErrorKind::QiniuApiError(ApiError::new(612, &headers, None)).into();
+
+        assert!(should_fail_over(&e503, true));
+        assert!(!should_fail_over(&e503, false));
+        assert!(is_host_failure(&e503));
+        assert!(!should_fail_over(&e579, true));
+        assert!(!should_fail_over(&e612, true));
+        assert!(!is_host_failure(&e612));
+    }
 }
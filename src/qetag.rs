@@ -0,0 +1,198 @@
+//! [Qiniu ETag][qetag] computation.
+//!
+//! [qetag]: https://developer.qiniu.com/kodo/manual/1231/appendix#qiniu-etag
+
+use std::io;
+use std::mem;
+
+use base64;
+use ring::digest;
+
+use super::errors::*;
+use super::storage::BLOCK_SIZE;
+
+
+const PREFIX_SINGLE_BLOCK: u8 = 0x16;
+const PREFIX_MULTI_BLOCK: u8 = 0x96;
+const PREFIX_MULTIPART_V2: u8 = 0x9e;
+
+
+/// Incremental ETag hasher.
+///
+/// Also implements `io::Write`, so data can be `io::copy`'d into it.
+pub struct Hasher {
+    block: digest::Context,
+    block_len: u64,
+    block_digests: Vec<u8>,
+}
+
+
+impl Default for Hasher {
+    fn default() -> Hasher {
+        Hasher::new()
+    }
+}
+
+
+impl Hasher {
+    pub fn new() -> Hasher {
+        Hasher {
+            block: digest::Context::new(&digest::SHA1),
+            block_len: 0,
+            block_digests: Vec::new(),
+        }
+    }
+
+    /// Feed more data into the hasher.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let remaining = (BLOCK_SIZE - self.block_len) as usize;
+            let n = ::std::cmp::min(data.len(), remaining);
+
+            self.block.update(&data[..n]);
+            self.block_len += n as u64;
+            data = &data[n..];
+
+            if self.block_len == BLOCK_SIZE {
+                let block = mem::replace(&mut self.block, digest::Context::new(&digest::SHA1));
+                self.block_digests.extend_from_slice(block.finish().as_ref());
+                self.block_len = 0;
+            }
+        }
+    }
+
+    fn finish_raw(mut self) -> Vec<u8> {
+        if self.block_len > 0 || self.block_digests.is_empty() {
+            self.block_digests.extend_from_slice(self.block.finish().as_ref());
+        }
+
+        let mut result = Vec::with_capacity(1 + digest::SHA1.output_len);
+        if self.block_digests.len() == digest::SHA1.output_len {
+            result.push(PREFIX_SINGLE_BLOCK);
+            result.extend_from_slice(&self.block_digests);
+        } else {
+            result.push(PREFIX_MULTI_BLOCK);
+            result.extend_from_slice(digest::digest(&digest::SHA1, &self.block_digests).as_ref());
+        }
+
+        result
+    }
+
+    /// Finish hashing, returning the ETag of all data fed.
+    pub fn finish(self) -> String {
+        base64::encode_config(&self.finish_raw(), base64::URL_SAFE)
+    }
+}
+
+
+impl io::Write for Hasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+/// Compute the ETag of the data.
+pub fn etag(data: &[u8]) -> String {
+    let mut h = Hasher::new();
+    h.update(data);
+    h.finish()
+}
+
+
+/// Compute the ETag of everything read from the reader.
+pub fn etag_from_reader<R: io::Read>(mut r: R) -> Result<String> {
+    let mut h = Hasher::new();
+    io::copy(&mut r, &mut h)?;
+    Ok(h.finish())
+}
+
+
+/// Compute the ETag of data uploaded with the v2 multipart protocol, using
+/// the given part sizes.
+///
+/// If every part but the last is exactly one block in size, or there is only
+/// one part, the result is the same as [etag].
+///
+/// [etag]: ./fn.etag.html
+pub fn etag_v2(data: &[u8], part_sizes: &[u64]) -> Result<String> {
+    let total = part_sizes.iter().fold(0u64, |acc, x| acc + x);
+    if total != data.len() as u64 {
+        bail!(
+            "part sizes sum to {} bytes, but data is {} bytes long",
+            total,
+            data.len()
+        );
+    }
+
+    let block_aligned = match part_sizes.split_last() {
+        Some((last, init)) => *last <= BLOCK_SIZE && init.iter().all(|&x| x == BLOCK_SIZE),
+        None => true,
+    };
+    if block_aligned || part_sizes.len() == 1 {
+        return Ok(etag(data));
+    }
+
+    let mut ctx = digest::Context::new(&digest::SHA1);
+    let mut offset = 0;
+    for &size in part_sizes {
+        let end = offset + size as usize;
+        let mut h = Hasher::new();
+        h.update(&data[offset..end]);
+        ctx.update(&h.finish_raw()[1..]);
+        offset = end;
+    }
+
+    let mut result = vec![PREFIX_MULTIPART_V2];
+    result.extend_from_slice(ctx.finish().as_ref());
+
+    Ok(base64::encode_config(&result, base64::URL_SAFE))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn test_data() -> Vec<u8> {
+        (0..(9 << 20)).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_etag() {
+        assert_eq!(etag(b""), "Fto5o-5ea0sNMlW_75VgGJCv2AcJ");
+        assert_eq!(etag(b"hello world"), "FiqubDXJT8-0FdvpX0CLnOke6Ebt");
+        assert_eq!(etag(&test_data()), "lv4Ew6JqZ47UmtoHTb4ntxxH-h5H");
+    }
+
+    #[test]
+    fn test_hasher_incremental() {
+        let data = test_data();
+        let mut h = Hasher::new();
+        for chunk in data.chunks(1234567) {
+            h.update(chunk);
+        }
+        assert_eq!(h.finish(), etag(&data));
+        assert_eq!(etag_from_reader(&data[..]).unwrap(), etag(&data));
+    }
+
+    #[test]
+    fn test_etag_v2() {
+        let data = test_data();
+        assert_eq!(
+            etag_v2(&data, &[1 << 20, 8 << 20]).unwrap(),
+            "nmR-XZlqyzLdzfT24e_DjRk5PGn8"
+        );
+        assert_eq!(
+            etag_v2(&data, &[4 << 20, 4 << 20, 1 << 20]).unwrap(),
+            etag(&data)
+        );
+        assert!(etag_v2(&data, &[1 << 20]).is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! [Regions][regions] (also known as zones) and their service hosts.
+//!
+//! [regions]: https://developer.qiniu.com/kodo/manual/1671/region-endpoint
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time;
+
+use url;
+
+use super::errors::*;
+use super::provider::{QiniuClient, QiniuHosts, QiniuHostsBuilder, Service};
+use super::request;
+use super::reqwest_compat as reqwest;
+
+
+/// Predefined storage regions.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Region {
+    /// East China.
+    Z0,
+    /// North China.
+    Z1,
+    /// South China.
+    Z2,
+    /// North America.
+    Na0,
+    /// Southeast Asia.
+    As0,
+}
+
+
+impl Region {
+    /// Returns the region ID, as used in the APIs.
+    pub fn id(&self) -> &'static str {
+        match self {
+            &Region::Z0 => "z0",
+            &Region::Z1 => "z1",
+            &Region::Z2 => "z2",
+            &Region::Na0 => "na0",
+            &Region::As0 => "as0",
+        }
+    }
+
+    /// Returns the region with the given ID, if known.
+    pub fn from_id(id: &str) -> Option<Region> {
+        match id {
+            "z0" => Some(Region::Z0),
+            "z1" => Some(Region::Z1),
+            "z2" => Some(Region::Z2),
+            "na0" => Some(Region::Na0),
+            "as0" => Some(Region::As0),
+            _ => None,
+        }
+    }
+
+    /// Returns the HTTPS hosts of services in the region.
+    pub fn hosts(&self) -> QiniuHosts {
+        // all of these are valid constants
+        QiniuHostsBuilder::new(*self).build().unwrap()
+    }
+
+    /// Returns the domains of the service, in order of preference.
+    pub(crate) fn domains(&self, service: Service) -> &'static [&'static str] {
+        match (self, service) {
+            (&Region::Z0, Service::Up) => &[
+                "up.qiniup.com",
+                "upload.qiniup.com",
+                "up-nb.qiniup.com",
+                "up-xs.qiniup.com",
+            ],
+            (&Region::Z0, Service::Io) => &["iovip.qbox.me"],
+            (&Region::Z0, Service::Rs) => &["rs.qiniu.com", "rs.qbox.me"],
+            (&Region::Z0, Service::Rsf) => &["rsf.qiniu.com", "rsf.qbox.me"],
+            (&Region::Z0, Service::Api) => &["api.qiniu.com"],
+
+            (&Region::Z1, Service::Up) => &["up-z1.qiniup.com", "upload-z1.qiniup.com"],
+            (&Region::Z1, Service::Io) => &["iovip-z1.qbox.me"],
+            (&Region::Z1, Service::Rs) => &["rs-z1.qiniu.com", "rs-z1.qbox.me"],
+            (&Region::Z1, Service::Rsf) => &["rsf-z1.qiniu.com", "rsf-z1.qbox.me"],
+            (&Region::Z1, Service::Api) => &["api-z1.qiniu.com"],
+
+            (&Region::Z2, Service::Up) => &[
+                "up-z2.qiniup.com",
+                "upload-z2.qiniup.com",
+                "up-gz.qiniup.com",
+                "up-fs.qiniup.com",
+            ],
+            (&Region::Z2, Service::Io) => &["iovip-z2.qbox.me"],
+            (&Region::Z2, Service::Rs) => &["rs-z2.qiniu.com", "rs-z2.qbox.me"],
+            (&Region::Z2, Service::Rsf) => &["rsf-z2.qiniu.com", "rsf-z2.qbox.me"],
+            (&Region::Z2, Service::Api) => &["api-z2.qiniu.com"],
+
+            (&Region::Na0, Service::Up) => &["up-na0.qiniup.com", "upload-na0.qiniup.com"],
+            (&Region::Na0, Service::Io) => &["iovip-na0.qbox.me"],
+            (&Region::Na0, Service::Rs) => &["rs-na0.qiniu.com", "rs-na0.qbox.me"],
+            (&Region::Na0, Service::Rsf) => &["rsf-na0.qiniu.com", "rsf-na0.qbox.me"],
+            (&Region::Na0, Service::Api) => &["api-na0.qiniu.com"],
+
+            (&Region::As0, Service::Up) => &["up-as0.qiniup.com", "upload-as0.qiniup.com"],
+            (&Region::As0, Service::Io) => &["iovip-as0.qbox.me"],
+            (&Region::As0, Service::Rs) => &["rs-as0.qiniu.com", "rs-as0.qbox.me"],
+            (&Region::As0, Service::Rsf) => &["rsf-as0.qiniu.com", "rsf-as0.qbox.me"],
+            (&Region::As0, Service::Api) => &["api-as0.qiniu.com"],
+
+            (_, Service::Uc) => &["uc.qbox.me"],
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+struct ServiceDomains {
+    domains: Vec<String>,
+    /// Backup domains.
+    old: Option<Vec<String>>,
+}
+
+
+impl ServiceDomains {
+    fn all(&self) -> Vec<&str> {
+        let old = self.old.as_ref().map_or(&[][..], |x| &x[..]);
+        self.domains.iter().chain(old).map(|x| x.as_str()).collect()
+    }
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+struct RegionHosts {
+    ttl: u64,
+    up: ServiceDomains,
+    io: ServiceDomains,
+    rs: ServiceDomains,
+    rsf: ServiceDomains,
+    api: ServiceDomains,
+    uc: Option<ServiceDomains>,
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RegionQueryResponse {
+    hosts: Vec<RegionHosts>,
+}
+
+
+impl RegionQueryResponse {
+    /// Returns the hosts and their TTL in seconds. The scheme of the uc
+    /// host that answered the query is used for all hosts.
+    pub(crate) fn into_hosts(self, uc: &url::Url) -> Result<(QiniuHosts, u64)> {
+        let h = match self.hosts.into_iter().next() {
+            Some(h) => h,
+            None => bail!("empty region query response"),
+        };
+
+        let mut builder = QiniuHostsBuilder::new(Region::Z0)
+            .https(uc.scheme() == "https")
+            .endpoints(Service::Up, &h.up.all())
+            .endpoints(Service::Io, &h.io.all())
+            .endpoints(Service::Rs, &h.rs.all())
+            .endpoints(Service::Rsf, &h.rsf.all())
+            .endpoints(Service::Api, &h.api.all())
+            .uc(&uc[url::Position::BeforeHost..url::Position::AfterPort]);
+        if let Some(ref uc) = h.uc {
+            builder = builder.endpoints(Service::Uc, &uc.all());
+        }
+
+        Ok((builder.build()?, h.ttl))
+    }
+}
+
+
+pub(crate) fn req_query(client: &QiniuClient, bucket: &str) -> Result<request::QiniuRequest> {
+    let url = {
+        let mut tmp = client.hosts().uc().join("v4/query")?;
+        {
+            let mut qs = tmp.query_pairs_mut();
+            qs.append_pair("ak", client.signer().access_key());
+            qs.append_pair("bucket", bucket);
+        }
+        tmp
+    };
+
+    Ok(
+        request::QiniuRequest::new(reqwest::Method::Get, url, None)?
+            .with_auth(request::Authorization::None)
+            .with_endpoints(client.hosts().endpoints(Service::Uc)),
+    )
+}
+
+
+/// In-memory cache of per-bucket hosts.
+#[derive(Default)]
+pub(crate) struct RegionCache {
+    inner: Mutex<HashMap<String, (time::Instant, QiniuHosts)>>,
+}
+
+
+impl RegionCache {
+    pub(crate) fn get(&self, bucket: &str) -> Option<QiniuHosts> {
+        let mut inner = match self.inner.lock() {
+            Ok(x) => x,
+            Err(_) => return None,
+        };
+
+        let expired = match inner.get(bucket) {
+            Some(&(deadline, ref hosts)) => {
+                if time::Instant::now() < deadline {
+                    return Some(hosts.clone());
+                }
+                true
+            }
+            None => false,
+        };
+        if expired {
+            inner.remove(bucket);
+        }
+
+        None
+    }
+
+    pub(crate) fn insert(&self, bucket: String, hosts: QiniuHosts, ttl: u64) {
+        if let Ok(mut inner) = self.inner.lock() {
+            let deadline = time::Instant::now() + time::Duration::from_secs(ttl);
+            inner.insert(bucket, (deadline, hosts));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+
+    #[test]
+    fn test_region() {
+        for r in &[Region::Z0, Region::Z1, Region::Z2, Region::Na0, Region::As0] {
+            assert_eq!(Region::from_id(r.id()), Some(*r));
+            r.hosts();
+        }
+        assert_eq!(Region::from_id("z9"), None);
+        assert_eq!(Region::Z1.hosts().up().as_str(), "https://up-z1.qiniup.com/");
+    }
+
+    #[test]
+    fn test_region_query_response() {
+        let resp = r#"{"hosts":[{"region":"z2","ttl":86400,
+            "io":{"domains":["iovip-z2.qbox.me"]},
+            "up":{"domains":["upload-z2.qiniup.com","up-z2.qiniup.com"],"old":["upload-z2.qbox.me"]},
+            "rs":{"domains":["rs-z2.qiniu.com"]},
+            "rsf":{"domains":["rsf-z2.qiniu.com"]},
+            "api":{"domains":["api-z2.qiniu.com"]}}]}"#;
+        let resp: RegionQueryResponse = serde_json::from_str(resp).unwrap();
+        let uc = Region::Z0.hosts().uc().clone();
+        let (hosts, ttl) = resp.into_hosts(&uc).unwrap();
+
+        assert_eq!(ttl, 86400);
+        assert_eq!(hosts.up().as_str(), "https://upload-z2.qiniup.com/");
+        assert_eq!(
+            hosts
+                .endpoints(Service::Up)
+                .iter()
+                .map(|u| u.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://upload-z2.qiniup.com/",
+                "https://up-z2.qiniup.com/",
+                "https://upload-z2.qbox.me/",
+            ]
+        );
+        assert_eq!(hosts.io().as_str(), "https://iovip-z2.qbox.me/");
+        assert_eq!(hosts.uc(), &uc);
+
+        let cache = RegionCache::default();
+        assert_eq!(cache.get("b"), None);
+        cache.insert("b".to_owned(), hosts.clone(), 60);
+        assert_eq!(cache.get("b"), Some(hosts));
+        cache.insert("b".to_owned(), Region::Z0.hosts(), 0);
+        assert_eq!(cache.get("b"), None);
+    }
+}
@@ -1,16 +1,36 @@
 use bytes;
 use url;
 
-use super::provider;
 use super::reqwest_compat as reqwest;
+use super::sign;
 
 use super::errors::*;
 
 
+/// Authorization scheme of a request.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Authorization {
+    /// No `Authorization` header is sent.
+    None,
+    /// `QBox` access token signed with the client credentials.
+    QBox,
+    /// `Qiniu` (v2) access token signed with the client credentials, covering
+    /// the method, host, content type and `X-Qiniu-*` headers as well.
+    Qiniu,
+    /// `UpToken` carrying the given upload token.
+    UpToken(String),
+}
+
+
 pub struct QiniuRequest {
     method: reqwest::Method,
     uri: url::Url,
     body: Option<bytes::Bytes>,
+    content_type: Option<String>,
+    headers: Vec<(String, String)>,
+    auth: Authorization,
+    endpoints: Vec<url::Url>,
+    idempotent: Option<bool>,
 }
 
 
@@ -24,28 +44,145 @@ impl QiniuRequest {
             method: method,
             uri: url::Url::parse(uri.as_ref())?,
             body: body,
+            content_type: None,
+            headers: Vec::new(),
+            auth: Authorization::QBox,
+            endpoints: Vec::new(),
+            idempotent: None,
         })
     }
 
-    pub(crate) fn into_lowlevel(self, client: &provider::QiniuClient) -> Result<reqwest::Request> {
-        let mut builder = client.reqwest_client().request(
-            self.method,
-            self.uri.as_ref(),
+    pub fn with_content_type<S: Into<String>>(mut self, content_type: S) -> QiniuRequest {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn with_header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> QiniuRequest {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn with_auth(mut self, auth: Authorization) -> QiniuRequest {
+        self.auth = auth;
+        self
+    }
+
+    /// Set the endpoints of the service the request is for, so the request
+    /// can be sent to the other ones if the host of `uri` fails.
+    pub fn with_endpoints(mut self, endpoints: &[url::Url]) -> QiniuRequest {
+        self.endpoints = endpoints.to_vec();
+        self
+    }
+
+    /// Mark the request as safe to send more than once or not, overriding
+    /// the default based on the method.
+    pub fn with_idempotent(mut self, idempotent: bool) -> QiniuRequest {
+        self.idempotent = Some(idempotent);
+        self
+    }
+
+    /// Returns if the request is safe to send more than once.
+    pub(crate) fn is_idempotent(&self) -> bool {
+        match self.idempotent {
+            Some(x) => x,
+            None => {
+                match self.method {
+                    reqwest::Method::Get |
+                    reqwest::Method::Head |
+                    reqwest::Method::Put |
+                    reqwest::Method::Delete |
+                    reqwest::Method::Options => true,
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    /// Returns the URI of the request followed by the URI rebased onto every
+    /// other endpoint of the service.
+    pub(crate) fn uris(&self) -> Vec<url::Url> {
+        let origin = self.uri.origin();
+        let mut tmp = vec![self.uri.clone()];
+        for endpoint in &self.endpoints {
+            if endpoint.origin() == origin {
+                continue;
+            }
+            if let Some(uri) = rebase(&self.uri, endpoint) {
+                tmp.push(uri);
+            }
+        }
+
+        tmp
+    }
+
+    /// Construct the low-level request to the given URI, which is the URI of
+    /// the request or one of its rebased versions.
+    pub(crate) fn to_lowlevel(
+        &self,
+        client: &reqwest::Client,
+        signer: &sign::QiniuSigner,
+        uri: &url::Url,
+    ) -> Result<reqwest::Request> {
+        let mut builder = client.request(
+            self.method.clone(),
+            uri.as_ref(),
         );
 
+        let content_type = match self.content_type {
+            None if self.auth == Authorization::Qiniu => Some("application/x-www-form-urlencoded".to_owned()),
+            ref x => x.clone(),
+        };
+
         // sign request
-        let auth_hdr = {
-            let signer = client.signer();
-            let mut tmp = String::from("QBox ");
-            // this clone is lightweight (maybe? due to the Arc inside)
-            let auth = signer.sign_req(&self.uri, self.body.clone().as_ref().map(|buf| &buf[..]));
-            tmp.push_str(&auth);
-            tmp
+        let auth_hdr = match self.auth {
+            Authorization::None => None,
+            Authorization::QBox => {
+                let mut tmp = String::from("QBox ");
+                let auth = signer.sign_req(uri, self.body.as_ref().map(|buf| &buf[..]));
+                tmp.push_str(&auth);
+                Some(tmp)
+            }
+            Authorization::Qiniu => {
+                let mut tmp = String::from("Qiniu ");
+                let headers: Vec<(&str, &str)> = self.headers
+                    .iter()
+                    .map(|&(ref k, ref v)| (k.as_str(), v.as_str()))
+                    .collect();
+                let auth = signer.sign_req_v2(
+                    self.method.as_ref(),
+                    uri,
+                    content_type.as_ref().map(|x| x.as_str()),
+                    &headers,
+                    self.body.as_ref().map(|buf| &buf[..]),
+                );
+                tmp.push_str(&auth);
+                Some(tmp)
+            }
+            Authorization::UpToken(ref token) => {
+                let mut tmp = String::from("UpToken ");
+                tmp.push_str(token);
+                Some(tmp)
+            }
         };
 
-        let builder = builder.header(reqwest::header::Authorization(auth_hdr));
+        let builder = if let Some(auth_hdr) = auth_hdr {
+            builder.header(reqwest::header::Authorization(auth_hdr))
+        } else {
+            &mut builder
+        };
 
-        let builder = if let Some(body) = self.body {
+        let builder = {
+            let mut headers = reqwest::header::Headers::new();
+            if let Some(content_type) = content_type {
+                headers.set_raw("Content-Type", content_type);
+            }
+            for &(ref k, ref v) in &self.headers {
+                headers.append_raw(k.clone(), v.clone());
+            }
+            builder.headers(headers)
+        };
+
+        let builder = if let Some(ref body) = self.body {
             // TODO
             builder.body(body.to_vec())
         } else {
@@ -55,3 +192,56 @@ impl QiniuRequest {
         Ok(builder.build()?)
     }
 }
+
+
+/// Replace the scheme, host and port of the URI with those of the endpoint.
+fn rebase(uri: &url::Url, endpoint: &url::Url) -> Option<url::Url> {
+    let mut tmp = uri.clone();
+    if tmp.set_scheme(endpoint.scheme()).is_err() || tmp.set_host(endpoint.host_str()).is_err() ||
+        tmp.set_port(endpoint.port()).is_err()
+    {
+        return None;
+    }
+
+    Some(tmp)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_uris() {
+        let endpoints = [
+            url::Url::parse("https://rs.qiniu.com").unwrap(),
+            url::Url::parse("https://rs.qbox.me").unwrap(),
+            url::Url::parse("http://127.0.0.1:9000").unwrap(),
+        ];
+        let req = QiniuRequest::new(reqwest::Method::Post, "https://rs.qiniu.com/stat/YTpi?x=1", None)
+            .unwrap()
+            .with_endpoints(&endpoints);
+
+        let uris = req.uris().into_iter().map(|u| u.into_string()).collect::<Vec<_>>();
+        assert_eq!(
+            uris,
+            vec![
+                "https://rs.qiniu.com/stat/YTpi?x=1",
+                "https://rs.qbox.me/stat/YTpi?x=1",
+                "http://127.0.0.1:9000/stat/YTpi?x=1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_idempotent() {
+        let req = |method| QiniuRequest::new(method, "https://rs.qiniu.com/", None).unwrap();
+
+        assert!(req(reqwest::Method::Get).is_idempotent());
+        assert!(req(reqwest::Method::Put).is_idempotent());
+        assert!(!req(reqwest::Method::Post).is_idempotent());
+        assert!(req(reqwest::Method::Post).with_idempotent(true).is_idempotent());
+        assert!(!req(reqwest::Method::Get).with_idempotent(false).is_idempotent());
+    }
+}
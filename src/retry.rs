@@ -0,0 +1,188 @@
+//! Retrying of failed requests with exponential backoff.
+
+use std::cmp;
+use std::time;
+
+use ring::rand::{self, SecureRandom};
+
+use super::errors::*;
+
+
+/// Policy of retrying requests failed with network errors or retryable
+/// statuses.
+///
+/// Each attempt tries every endpoint of the service before giving up, so
+/// this is about transient failures of the whole service. Requests that are
+/// not idempotent are not retried unless enabled with [retry_non_idempotent],
+/// and only fail over to other endpoints if they could not connect.
+///
+/// [retry_non_idempotent]: #method.retry_non_idempotent
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: time::Duration,
+    max_delay: time::Duration,
+    retryable_statuses: Vec<u16>,
+    retry_non_idempotent: bool,
+}
+
+
+impl Default for RetryPolicy {
+    /// 3 attempts with delays starting at 100ms and capped at 3s, retrying
+    /// idempotent requests on 500, 502, 503, 504, 571, 573 and 599.
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: time::Duration::from_millis(100),
+            max_delay: time::Duration::from_secs(3),
+            retryable_statuses: vec![500, 502, 503, 504, 571, 573, 599],
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+
+impl RetryPolicy {
+    /// Policy of never retrying.
+    pub fn none() -> RetryPolicy {
+        RetryPolicy::default().max_attempts(1)
+    }
+
+    /// Set the maximum number of attempts, including the first one.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = cmp::max(1, max_attempts);
+        self
+    }
+
+    /// Set the delay before the first retry, doubled for every following
+    /// retry up to `max_delay`. A random jitter of up to half the delay is
+    /// subtracted.
+    pub fn backoff(mut self, base_delay: time::Duration, max_delay: time::Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = cmp::max(base_delay, max_delay);
+        self
+    }
+
+    /// Set the HTTP statuses to retry on.
+    pub fn retryable_statuses(mut self, statuses: &[u16]) -> Self {
+        self.retryable_statuses = statuses.to_vec();
+        self
+    }
+
+    /// Retry requests that are not idempotent as well, e.g. deleting, moving
+    /// or uploading without overwriting.
+    pub fn retry_non_idempotent(mut self, retry: bool) -> Self {
+        self.retry_non_idempotent = retry;
+        self
+    }
+
+    /// Returns if the request should be attempted again after failing the
+    /// given number of times.
+    pub(crate) fn should_retry(&self, e: &Error, idempotent: bool, attempts: u32) -> bool {
+        if attempts >= self.max_attempts || (!idempotent && !self.retry_non_idempotent) {
+            return false;
+        }
+
+        self.is_retryable(e)
+    }
+
+    /// Returns the error the request finally failed with, marking it as
+    /// having happened after the given number of attempts only if it would
+    /// have been retried with attempts left. Other errors pass through.
+    pub(crate) fn give_up(&self, e: Error, attempts: u32) -> Error {
+        if attempts <= 1 || attempts < self.max_attempts || !self.is_retryable(&e) {
+            return e;
+        }
+
+        ErrorKind::RetriesExhausted(attempts, Box::new(e)).into()
+    }
+
+    fn is_retryable(&self, e: &Error) -> bool {
+        match e.kind() {
+            &ErrorKind::ReqwestError(_) => true,
+            &ErrorKind::QiniuApiError(ref e) => self.retryable_statuses.contains(&e.status),
+            _ => false,
+        }
+    }
+
+    /// Returns the delay before the next attempt after failing the given
+    /// number of times.
+    pub(crate) fn delay(&self, attempts: u32) -> time::Duration {
+        let shift = cmp::min(attempts.saturating_sub(1), 16);
+        let delay = self.base_delay.checked_mul(1 << shift).unwrap_or(
+            self.max_delay,
+        );
+        let delay = cmp::min(delay, self.max_delay);
+
+        // "equal jitter", keeping at least half of the delay
+        let half = delay / 2;
+        half + jitter(delay - half)
+    }
+}
+
+
+/// Random duration in `[0, max]`.
+fn jitter(max: time::Duration) -> time::Duration {
+    let mut buf = [0u8; 4];
+    if rand::SystemRandom::new().fill(&mut buf).is_err() {
+        return time::Duration::from_secs(0);
+    }
+    let x = (buf[0] as u32) << 24 | (buf[1] as u32) << 16 | (buf[2] as u32) << 8 | buf[3] as u32;
+
+    let max_ms = max.as_secs() * 1000 + (max.subsec_nanos() / 1_000_000) as u64;
+    time::Duration::from_millis(max_ms * x as u64 / u32::max_value() as u64)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::reqwest_compat as reqwest;
+
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::default();
+        let headers = reqwest::header::Headers::new();
+        let e503: Error = ErrorKind::QiniuApiError(ApiError::new(503, &headers, None)).into();
+        let e612: Error = ErrorKind::QiniuApiError(ApiError::new(612, &headers, None)).into();
+
+        assert!(policy.should_retry(&e503, true, 1));
+        assert!(policy.should_retry(&e503, true, 2));
+        assert!(!policy.should_retry(&e503, true, 3));
+        assert!(!policy.should_retry(&e503, false, 1));
+        assert!(!policy.should_retry(&e612, true, 1));
+        assert!(policy.clone().retry_non_idempotent(true).should_retry(&e503, false, 1));
+        assert!(!RetryPolicy::none().should_retry(&e503, true, 1));
+        assert!(!policy.retryable_statuses(&[]).should_retry(&e503, true, 1));
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::default().backoff(time::Duration::from_millis(100), time::Duration::from_millis(1000));
+        for &(attempts, min, max) in &[(1, 50, 100), (2, 100, 200), (3, 200, 400), (5, 500, 1000), (100, 500, 1000)] {
+            let d = policy.delay(attempts);
+            assert!(d >= time::Duration::from_millis(min), "{:?}", d);
+            assert!(d <= time::Duration::from_millis(max), "{:?}", d);
+        }
+    }
+
+    #[test]
+    fn test_give_up() {
+        let policy = RetryPolicy::default();
+        let headers = reqwest::header::Headers::new();
+        let e503 = || -> Error { ErrorKind::QiniuApiError(ApiError::new(503, &headers, None)).into() };
+        let e612 = || -> Error { ErrorKind::QiniuApiError(ApiError::new(612, &headers, None)).into() };
+
+        let e = policy.give_up(e503(), 3);
+        assert_eq!(e.api_error().map(|e| e.status), Some(503));
+        assert_eq!(format!("{}", e), "failed after 3 attempts: Qiniu API error: status 503");
+
+        // a 612 after an earlier 503 is not retried, whatever the attempts
+        for &attempts in &[1, 2, 3] {
+            let e = policy.give_up(e612(), attempts);
+            assert_eq!(format!("{}", e), "Qiniu API error: status 612");
+        }
+        assert_eq!(format!("{}", policy.give_up(e503(), 1)), "Qiniu API error: status 503");
+    }
+}
@@ -1,8 +1,11 @@
+//! Signing with Qiniu access and secret keys.
+
 use base64;
 use ring;
 use url;
 
 
+/// Signer holding a pair of access key and secret key.
 pub struct QiniuSigner {
     ak: String,
     sk: ring::hmac::SigningKey,
@@ -19,6 +22,10 @@ impl QiniuSigner {
         }
     }
 
+    pub fn access_key(&self) -> &str {
+        &self.ak
+    }
+
     pub fn sign_blob(&self, blob: &[u8]) -> String {
         let mut ctx = ring::hmac::SigningContext::with_key(&self.sk);
 
@@ -58,6 +65,106 @@ impl QiniuSigner {
             tmp
         }
     }
+
+    /// Sign the request with the `Qiniu` (v2) algorithm.
+    ///
+    /// `headers` may contain any headers, only the `X-Qiniu-*` ones are
+    /// signed. The body is signed unless there is no content type or the
+    /// content type is `application/octet-stream`.
+    pub fn sign_req_v2(
+        &self,
+        method: &str,
+        url: &url::Url,
+        content_type: Option<&str>,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> String {
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => String::new(),
+        };
+        let path = match url.query() {
+            Some(qs) => format!("{}?{}", url.path(), qs),
+            None => url.path().to_owned(),
+        };
+
+        self.sign_data_v2(method, &path, &host, content_type, headers, body)
+    }
+
+    fn sign_data_v2(
+        &self,
+        method: &str,
+        path: &str,
+        host: &str,
+        content_type: Option<&str>,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> String {
+        let mut ctx = ring::hmac::SigningContext::with_key(&self.sk);
+        ctx.update(method.as_bytes());
+        ctx.update(b" ");
+        ctx.update(path.as_bytes());
+        ctx.update(b"\nHost: ");
+        ctx.update(host.as_bytes());
+        if let Some(ct) = content_type {
+            ctx.update(b"\nContent-Type: ");
+            ctx.update(ct.as_bytes());
+        }
+
+        let mut x_headers: Vec<(String, &str)> = headers
+            .iter()
+            .map(|&(k, v)| (canonical_header_key(k), v))
+            .filter(|&(ref k, _)| k.len() > X_QINIU_PREFIX.len() && k.starts_with(X_QINIU_PREFIX))
+            .collect();
+        // stable sort, so the first value wins for repeated headers
+        x_headers.sort_by(|a, b| a.0.cmp(&b.0));
+        x_headers.dedup_by_key(|x| x.0.clone());
+        for (k, v) in x_headers {
+            ctx.update(b"\n");
+            ctx.update(k.as_bytes());
+            ctx.update(b": ");
+            ctx.update(v.as_bytes());
+        }
+        ctx.update(b"\n\n");
+
+        match (content_type, body) {
+            (Some(ct), Some(body)) if ct != "application/octet-stream" => ctx.update(body),
+            _ => {}
+        }
+
+        let digest = ctx.sign();
+        let digest = digest.as_ref();
+
+        {
+            let mut tmp = self.ak.clone();
+            tmp.push(':');
+            base64::encode_config_buf(digest, base64::URL_SAFE, &mut tmp);
+
+            tmp
+        }
+    }
+}
+
+
+const X_QINIU_PREFIX: &'static str = "X-Qiniu-";
+
+
+/// Canonicalize the header name in MIME style, e.g. `x-qiniu-foo` to
+/// `X-Qiniu-Foo`.
+fn canonical_header_key(k: &str) -> String {
+    let mut tmp = String::with_capacity(k.len());
+    let mut upper = true;
+    for c in k.chars() {
+        if upper {
+            tmp.extend(c.to_uppercase());
+        } else {
+            tmp.extend(c.to_lowercase());
+        }
+        upper = c == '-';
+    }
+
+    tmp
 }
 
 
@@ -88,4 +195,68 @@ mod tests {
             "MY_ACCESS_KEY:438dd8pXocjYuF-6dTcKMtETB2g="
         );
     }
+
+    #[test]
+    fn test_sign_data_v2() {
+        let x = QiniuSigner::new("ak", "sk");
+        let body = br#"{"name": "test"}"#;
+
+        let headers = [
+            ("X-Qiniu-Bbb", "BBB"),
+            ("X-Qiniu-Bbb", "AAA"),
+            ("X-Qiniu-Aaa", "DDD"),
+            ("X-Qiniu-Aaa", "CCC"),
+            ("X-Qiniu-", "a"),
+            ("X-Qiniu", "b"),
+        ];
+        assert_eq!(
+            x.sign_data_v2(
+                "GET",
+                "",
+                "upload.qiniup.com",
+                Some("application/json"),
+                &headers,
+                Some(body),
+            ),
+            "ak:DhNA1UCaBqSHCsQjMOLRfVn63GQ="
+        );
+
+        let headers = [("X-Qiniu-", "a"), ("X-Qiniu", "b")];
+        assert_eq!(
+            x.sign_data_v2(
+                "GET",
+                "",
+                "upload.qiniup.com",
+                Some("application/x-www-form-urlencoded"),
+                &headers,
+                Some(body),
+            ),
+            "ak:GShw5NitGmd5TLoo38nDkGUofRw="
+        );
+    }
+
+    #[test]
+    fn test_canonical_header_key() {
+        assert_eq!(canonical_header_key("x-qiniu-foo-BAR"), "X-Qiniu-Foo-Bar");
+        assert_eq!(canonical_header_key("Content-Type"), "Content-Type");
+    }
+
+    #[test]
+    fn test_sign_req_v2() {
+        let x = QiniuSigner::new("MY_ACCESS_KEY", "MY_SECRET_KEY");
+
+        let url = "https://rs.qiniu.com:8080/move/bmV3ZG9jczpmaW5kX21hbi50eHQ=/bmV3ZG9jczpmaW5kLm1hbi50eHQ=?force=true";
+        let url = url::Url::parse(url).unwrap();
+        let headers = [("x-qiniu-date", "20171018T000000Z"), ("Accept", "*/*")];
+        assert_eq!(
+            x.sign_req_v2(
+                "POST",
+                &url,
+                Some("application/x-www-form-urlencoded"),
+                &headers,
+                None,
+            ),
+            "MY_ACCESS_KEY:d91MuHh0Kv0hdqRRA9jX5kE7LCA="
+        );
+    }
 }
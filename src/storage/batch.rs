@@ -0,0 +1,239 @@
+//! [Batch operations][batch] on objects.
+//!
+//! [batch]: https://developer.qiniu.com/kodo/api/1250/batch
+
+use serde_json;
+use url;
+
+#[cfg(feature = "async-api")]
+use futures::stream;
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+use super::manage;
+use super::types::StorageKind;
+
+
+/// Maximum number of operations in a single batch request.
+pub const MAX_BATCH_OPS: usize = 1000;
+
+
+/// Result of a single operation in a batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchOpResult {
+    /// HTTP status code of the operation.
+    pub code: u16,
+    /// Response body of the operation, e.g. the object metadata for stat
+    /// operations, or an object with an `error` field on failure.
+    pub data: Option<serde_json::Value>,
+}
+
+
+impl BatchOpResult {
+    /// Returns if the operation succeeded.
+    pub fn is_success(&self) -> bool {
+        self.code == 200
+    }
+
+    /// Returns the error message of the failed operation, if any.
+    pub fn error(&self) -> Option<&str> {
+        self.data.as_ref().and_then(|d| d.get("error")).and_then(
+            |e| e.as_str(),
+        )
+    }
+}
+
+
+/// Form bodies of the batch requests, with at most [MAX_BATCH_OPS]
+/// operations each.
+///
+/// [MAX_BATCH_OPS]: ./constant.MAX_BATCH_OPS.html
+fn batch_bodies(ops: &[String]) -> Vec<String> {
+    ops.chunks(MAX_BATCH_OPS)
+        .map(|ops| {
+            let mut tmp = url::form_urlencoded::Serializer::new(String::new());
+            for op in ops {
+                tmp.append_pair("op", op);
+            }
+            tmp.finish()
+        })
+        .collect()
+}
+
+
+/// Builder of batch operations.
+///
+/// Obtain one with [QiniuStorageClient::batch]. Operations are sent in as
+/// many requests as needed to stay within [MAX_BATCH_OPS], and results are
+/// returned in the order the operations were added.
+///
+/// [QiniuStorageClient::batch]: ./struct.QiniuStorageClient.html#method.batch
+/// [MAX_BATCH_OPS]: ./constant.MAX_BATCH_OPS.html
+pub struct Batch<'a> {
+    provider: &'a provider::QiniuClient,
+    hosts: provider::QiniuHosts,
+    ops: Vec<String>,
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    /// Begin constructing a batch of operations.
+    pub fn batch(&self) -> Batch<'a> {
+        Batch {
+            provider: self.provider,
+            hosts: self.hosts().clone(),
+            ops: Vec::new(),
+        }
+    }
+}
+
+
+impl<'a> Batch<'a> {
+    /// Returns the number of operations in the batch.
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Returns if there are no operations in the batch.
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn stat(mut self, bucket: &str, key: &str) -> Self {
+        self.ops.push(manage::stat_op(bucket, key));
+        self
+    }
+
+    pub fn delete(mut self, bucket: &str, key: &str) -> Self {
+        self.ops.push(manage::delete_op(bucket, key));
+        self
+    }
+
+    pub fn move_(mut self, src_bucket: &str, src_key: &str, dest_bucket: &str, dest_key: &str, force: bool) -> Self {
+        self.ops.push(manage::move_op(
+            src_bucket,
+            src_key,
+            dest_bucket,
+            dest_key,
+            force,
+        ));
+        self
+    }
+
+    pub fn copy(mut self, src_bucket: &str, src_key: &str, dest_bucket: &str, dest_key: &str, force: bool) -> Self {
+        self.ops.push(manage::copy_op(
+            src_bucket,
+            src_key,
+            dest_bucket,
+            dest_key,
+            force,
+        ));
+        self
+    }
+
+    pub fn change_mime(mut self, bucket: &str, key: &str, mime_type: &str) -> Self {
+        self.ops.push(manage::change_mime_op(bucket, key, mime_type));
+        self
+    }
+
+    pub fn change_type(mut self, bucket: &str, key: &str, kind: StorageKind) -> Self {
+        self.ops.push(manage::change_type_op(bucket, key, kind));
+        self
+    }
+
+    /// Request of the body of the operations, retried only if all of them
+    /// are idempotent.
+    fn req_batch(hosts: &provider::QiniuHosts, ops: &[String], body: String) -> Result<request::QiniuRequest> {
+        let url = hosts.rs().join("batch")?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, Some(body.into()))?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(hosts.endpoints(provider::Service::Rs))
+                .with_idempotent(ops.iter().all(|op| manage::is_idempotent_op(op))),
+        )
+    }
+
+    /// Execute all operations.
+    #[cfg(feature = "async-api")]
+    pub fn execute(self) -> impl Future<Item = Vec<BatchOpResult>, Error = Error> + 'a {
+        let provider = self.provider;
+        let hosts = &self.hosts;
+        let reqs = self.ops
+            .chunks(MAX_BATCH_OPS)
+            .zip(batch_bodies(&self.ops))
+            .map(|(ops, body)| Batch::req_batch(hosts, ops, body))
+            .collect::<Vec<_>>();
+
+        let x = stream::iter_ok(reqs).and_then(move |req| client::execute_json(provider, req));
+        let x = x.fold(Vec::with_capacity(self.ops.len()), |mut acc, results: Vec<BatchOpResult>| {
+            acc.extend(results);
+            Ok::<_, Error>(acc)
+        });
+
+        x
+    }
+
+    /// Execute all operations.
+    #[cfg(feature = "sync-api")]
+    pub fn execute(self) -> Result<Vec<BatchOpResult>> {
+        let mut acc = Vec::with_capacity(self.ops.len());
+        for (ops, body) in self.ops.chunks(MAX_BATCH_OPS).zip(batch_bodies(&self.ops)) {
+            let req = Batch::req_batch(&self.hosts, ops, body);
+            let results: Vec<BatchOpResult> = client::execute_json(self.provider, req)?;
+            acc.extend(results);
+        }
+
+        Ok(acc)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_batch_bodies() {
+        let ops = vec![manage::stat_op("b", "a.jpg"), manage::delete_op("b", "a b")];
+        assert_eq!(
+            batch_bodies(&ops),
+            vec!["op=%2Fstat%2FYjphLmpwZw%3D%3D&op=%2Fdelete%2FYjphIGI%3D".to_owned()]
+        );
+        assert!(batch_bodies(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_batch_splitting() {
+        let ops = (0..MAX_BATCH_OPS + 1)
+            .map(|i| manage::delete_op("b", &i.to_string()))
+            .collect::<Vec<_>>();
+        let count = |body: &String| url::form_urlencoded::parse(body.as_bytes()).count();
+
+        let bodies = batch_bodies(&ops[..MAX_BATCH_OPS]);
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(count(&bodies[0]), MAX_BATCH_OPS);
+
+        let bodies = batch_bodies(&ops);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(count(&bodies[0]), MAX_BATCH_OPS);
+        assert_eq!(count(&bodies[1]), 1);
+        assert_eq!(bodies[1], "op=%2Fdelete%2FYjoxMDAw");
+    }
+
+    #[test]
+    fn test_req_batch_idempotent() {
+        let hosts = provider::QiniuHosts::default();
+        let req = |ops: Vec<String>| Batch::req_batch(&hosts, &ops, batch_bodies(&ops).remove(0)).unwrap();
+
+        assert!(req(vec![manage::stat_op("b", "a"), manage::change_mime_op("b", "c", "text/plain")]).is_idempotent());
+        assert!(!req(vec![manage::stat_op("b", "a"), manage::delete_op("b", "c")]).is_idempotent());
+        assert!(!req(vec![manage::move_op("b", "a", "b", "c", true)]).is_idempotent());
+    }
+}
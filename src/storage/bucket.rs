@@ -0,0 +1,351 @@
+//! [Bucket management][bucket], i.e. creation, deletion, access control and
+//! information of buckets.
+//!
+//! [bucket]: https://developer.qiniu.com/kodo/api/3926/get-service
+
+use std::time;
+
+#[cfg(feature = "async-api")]
+use futures::future;
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::region::Region;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+use super::types::StorageKind;
+
+
+/// Information of a bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketInfo {
+    /// Region the bucket is in, if known to this crate.
+    pub region: Option<Region>,
+    /// If the bucket is private, i.e. downloads need signed URLs.
+    pub private: bool,
+    /// Number of objects of every storage kind, as of the last daily
+    /// statistics. Kinds without statistics for the bucket are missing.
+    pub object_counts: Vec<(StorageKind, u64)>,
+}
+
+
+impl BucketInfo {
+    /// Returns the number of objects of the storage kind, if known.
+    pub fn object_count(&self, kind: StorageKind) -> Option<u64> {
+        self.object_counts.iter().find(|x| x.0 == kind).map(|x| x.1)
+    }
+}
+
+
+/// Response of the `v2/bucketInfo` API, of which only the fields used by
+/// this crate are decoded.
+#[derive(Deserialize)]
+pub(super) struct RawBucketInfo {
+    pub source: Option<String>,
+    pub host: Option<String>,
+    pub private: Option<u8>,
+    pub region: Option<String>,
+    pub zone: Option<String>,
+}
+
+
+/// Response of the statistics APIs.
+#[derive(Deserialize)]
+struct StatisticsResponse {
+    datas: Option<Vec<Option<u64>>>,
+}
+
+
+impl StatisticsResponse {
+    fn last(&self) -> Option<u64> {
+        self.datas.as_ref().and_then(
+            |d| d.iter().rev().filter_map(|&x| x).next(),
+        )
+    }
+}
+
+
+/// Treat statistics the API has none of for the bucket as missing, and pass
+/// other errors through.
+fn statistics_or_missing(resp: Result<StatisticsResponse>) -> Result<Option<StatisticsResponse>> {
+    match resp {
+        Ok(x) => Ok(Some(x)),
+        Err(ref e) if e.api_error().map_or(false, |e| e.status == 404 || e.status == 612) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+
+/// Storage kinds with object count statistics, and their API paths.
+const COUNTED_KINDS: &'static [(StorageKind, &'static str)] = &[
+    (StorageKind::Conventional, "v6/count"),
+    (StorageKind::LowFrequency, "v6/count_line"),
+    (StorageKind::Archive, "v6/count_archive"),
+    (StorageKind::DeepArchive, "v6/count_deep_archive"),
+];
+
+
+impl BucketInfo {
+    /// Takes the statistics of `COUNTED_KINDS` in order, `None` for those
+    /// that are missing.
+    fn new(raw: RawBucketInfo, counts: Vec<Option<StatisticsResponse>>) -> BucketInfo {
+        BucketInfo {
+            region: raw.region.or(raw.zone).and_then(|x| Region::from_id(&x)),
+            private: raw.private.unwrap_or(0) != 0,
+            object_counts: COUNTED_KINDS
+                .iter()
+                .zip(counts)
+                .filter_map(|(&(kind, _), resp)| {
+                    resp.and_then(|x| x.last()).map(|x| (kind, x))
+                })
+                .collect(),
+        }
+    }
+}
+
+
+/// Format the Unix timestamp as `yyyyMMddHHmmss` in UTC, as used by the
+/// statistics APIs.
+fn statistics_time(ts: u64) -> String {
+    let (days, secs) = (ts / 86400, ts % 86400);
+
+    // civil_from_days of http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        y,
+        m,
+        d,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    fn req_uc(&self, path: &str) -> Result<request::QiniuRequest> {
+        let url = self.hosts().uc().join(path)?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Uc)),
+        )
+    }
+
+    fn req_create_bucket(&self, bucket: &str, region: Region) -> Result<request::QiniuRequest> {
+        let path = format!(
+            "mkbucketv3/{}/region/{}",
+            client::bucket_segment(bucket)?,
+            region.id()
+        );
+        self.req_uc(&path)
+    }
+
+    fn req_drop_bucket(&self, bucket: &str) -> Result<request::QiniuRequest> {
+        let path = format!("drop/{}", client::bucket_segment(bucket)?);
+        self.req_uc(&path)
+    }
+
+    pub(super) fn req_bucket_info(&self, bucket: &str) -> Result<request::QiniuRequest> {
+        let url = {
+            let mut tmp = self.hosts().uc().join("v2/bucketInfo")?;
+            tmp.query_pairs_mut().append_pair("bucket", bucket);
+            tmp
+        };
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Uc))
+                .with_idempotent(true),
+        )
+    }
+
+    fn req_bucket_statistics(&self, bucket: &str, path: &str) -> Result<request::QiniuRequest> {
+        let now = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+
+        let url = {
+            let mut tmp = self.hosts().api().join(path)?;
+            {
+                let mut qs = tmp.query_pairs_mut();
+                qs.append_pair("bucket", bucket);
+                qs.append_pair("begin", &statistics_time(now.saturating_sub(2 * 86400)));
+                qs.append_pair("end", &statistics_time(now));
+                qs.append_pair("g", "day");
+            }
+            tmp
+        };
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Get, url, None)?
+                .with_endpoints(self.hosts().endpoints(provider::Service::Api)),
+        )
+    }
+
+    fn req_set_bucket_private(&self, bucket: &str, private: bool) -> Result<request::QiniuRequest> {
+        let url = {
+            let mut tmp = self.hosts().uc().join("private")?;
+            {
+                let mut qs = tmp.query_pairs_mut();
+                qs.append_pair("bucket", bucket);
+                qs.append_pair("private", if private { "1" } else { "0" });
+            }
+            tmp
+        };
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Uc))
+                .with_idempotent(true),
+        )
+    }
+}
+
+
+#[cfg(feature = "async-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Create the bucket in the region.
+    pub fn create_bucket(&self, bucket: &str, region: Region) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_create_bucket(bucket, region);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Delete the bucket, which must be empty.
+    pub fn drop_bucket(&self, bucket: &str) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_drop_bucket(bucket);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Make the bucket private or public.
+    pub fn set_bucket_private(&self, bucket: &str, private: bool) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_set_bucket_private(bucket, private);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Returns information of the bucket.
+    pub fn bucket_info(&self, bucket: &str) -> impl Future<Item = BucketInfo, Error = Error> {
+        let info = client::execute_json(self.provider, self.req_bucket_info(bucket));
+        let counts = COUNTED_KINDS
+            .iter()
+            .map(|&(_, path)| {
+                client::execute_json(self.provider, self.req_bucket_statistics(bucket, path))
+                    .then(statistics_or_missing)
+            })
+            .collect::<Vec<_>>();
+
+        info.join(future::join_all(counts)).map(|(raw, counts)| {
+            BucketInfo::new(raw, counts)
+        })
+    }
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Create the bucket in the region.
+    pub fn create_bucket(&self, bucket: &str, region: Region) -> Result<()> {
+        let req = self.req_create_bucket(bucket, region);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Delete the bucket, which must be empty.
+    pub fn drop_bucket(&self, bucket: &str) -> Result<()> {
+        let req = self.req_drop_bucket(bucket);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Make the bucket private or public.
+    pub fn set_bucket_private(&self, bucket: &str, private: bool) -> Result<()> {
+        let req = self.req_set_bucket_private(bucket, private);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Returns information of the bucket.
+    pub fn bucket_info(&self, bucket: &str) -> Result<BucketInfo> {
+        let raw = client::execute_json(self.provider, self.req_bucket_info(bucket))?;
+        let mut counts = Vec::with_capacity(COUNTED_KINDS.len());
+        for &(_, path) in COUNTED_KINDS {
+            counts.push(statistics_or_missing(client::execute_json(
+                self.provider,
+                self.req_bucket_statistics(bucket, path),
+            ))?);
+        }
+
+        Ok(BucketInfo::new(raw, counts))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+
+    #[test]
+    fn test_statistics_time() {
+        assert_eq!(statistics_time(0), "19700101000000");
+        assert_eq!(statistics_time(951825845), "20000229120405");
+        assert_eq!(statistics_time(1539820800), "20181018000000");
+    }
+
+    #[test]
+    fn test_bucket_info() {
+        let raw: RawBucketInfo = serde_json::from_str(r#"{"source":"","private":1,"zone":"z1","region":"z1"}"#).unwrap();
+        let counts = vec![
+            Some(serde_json::from_str(r#"{"times":[1,2],"datas":[100,null]}"#).unwrap()),
+            Some(serde_json::from_str(r#"{"times":[],"datas":[]}"#).unwrap()),
+            None,
+        ];
+        let info = BucketInfo::new(raw, counts);
+
+        assert_eq!(info.region, Some(Region::Z1));
+        assert!(info.private);
+        assert_eq!(info.object_count(StorageKind::Conventional), Some(100));
+        assert_eq!(info.object_count(StorageKind::LowFrequency), None);
+        assert_eq!(info.object_count(StorageKind::Archive), None);
+        assert_eq!(info.object_count(StorageKind::DeepArchive), None);
+    }
+
+    #[test]
+    fn test_bucket_info_unknown_region() {
+        let raw: RawBucketInfo = serde_json::from_str(r#"{"source":"","zone":"z9"}"#).unwrap();
+        let info = BucketInfo::new(raw, vec![]);
+
+        assert_eq!(info.region, None);
+        assert!(!info.private);
+        assert_eq!(info.object_count(StorageKind::Conventional), None);
+    }
+
+    #[test]
+    fn test_statistics_or_missing() {
+        let headers = reqwest::header::Headers::new();
+        let api_error = |status| -> Result<StatisticsResponse> {
+            Err(ErrorKind::QiniuApiError(ApiError::new(status, &headers, None)).into())
+        };
+
+        assert!(statistics_or_missing(api_error(404)).unwrap().is_none());
+        assert!(statistics_or_missing(api_error(612)).unwrap().is_none());
+        assert!(statistics_or_missing(api_error(401)).is_err());
+        assert!(statistics_or_missing(Err("oops".into())).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Verification of [upload callbacks][callback] sent to app servers.
+//!
+//! [callback]: https://developer.qiniu.com/kodo/manual/1206/put-policy#put-policy-callback-url
+
+use ring;
+use url;
+
+use super::super::sign;
+use super::client::QiniuStorageClient;
+
+
+const FORM_CONTENT_TYPE: &'static str = "application/x-www-form-urlencoded";
+
+
+/// Returns if the `Authorization` header value is a valid signature of the
+/// callback request, in either `QBox` or `Qiniu` format.
+fn verify_callback(
+    signer: &sign::QiniuSigner,
+    authorization: &str,
+    method: &str,
+    url: &url::Url,
+    content_type: Option<&str>,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> bool {
+    let expected = if authorization.starts_with("QBox ") {
+        // body is only signed for form submissions in the QBox format
+        let body = match content_type {
+            Some(FORM_CONTENT_TYPE) => Some(body),
+            _ => None,
+        };
+
+        let mut tmp = String::from("QBox ");
+        tmp.push_str(&signer.sign_req(url, body));
+        tmp
+    } else if authorization.starts_with("Qiniu ") {
+        let mut tmp = String::from("Qiniu ");
+        tmp.push_str(&signer.sign_req_v2(
+            method,
+            url,
+            content_type,
+            headers,
+            Some(body),
+        ));
+        tmp
+    } else {
+        return false;
+    };
+
+    ring::constant_time::verify_slices_are_equal(expected.as_bytes(), authorization.as_bytes()).is_ok()
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    /// Returns if the callback request received by the app server is
+    /// genuinely from Qiniu, i.e. signed with the credentials of this client.
+    ///
+    /// `url` is the full URL the request was received at, including the
+    /// query string. `headers` must contain the `Authorization` header, and
+    /// should contain all `X-Qiniu-*` headers for requests signed in the
+    /// `Qiniu` format.
+    pub fn verify_callback(
+        &self,
+        method: &str,
+        url: &str,
+        content_type: Option<&str>,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> bool {
+        let url = match url::Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return false,
+        };
+        let authorization = headers.iter().find(|&&(k, _)| {
+            k.eq_ignore_ascii_case("Authorization")
+        });
+        let authorization = match authorization {
+            Some(&(_, v)) => v,
+            None => return false,
+        };
+
+        verify_callback(
+            self.provider.signer(),
+            authorization,
+            method,
+            &url,
+            content_type,
+            headers,
+            body,
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_verify_callback() {
+        let signer = sign::QiniuSigner::new("MY_ACCESS_KEY", "MY_SECRET_KEY");
+        let url = url::Url::parse("https://app.example.com/callback?a=1").unwrap();
+        let body = b"key=sunflower.jpg&hash=Fto5o-5ea0sNMlW_75VgGJCv2AcJ";
+
+        let mut qbox = String::from("QBox ");
+        qbox.push_str(&signer.sign_req(&url, Some(body)));
+        assert!(verify_callback(
+            &signer,
+            &qbox,
+            "POST",
+            &url,
+            Some(FORM_CONTENT_TYPE),
+            &[],
+            body,
+        ));
+        assert!(!verify_callback(
+            &signer,
+            &qbox,
+            "POST",
+            &url,
+            Some(FORM_CONTENT_TYPE),
+            &[],
+            b"key=evil.jpg",
+        ));
+
+        let headers = [("X-Qiniu-Foo", "bar")];
+        let mut v2 = String::from("Qiniu ");
+        v2.push_str(&signer.sign_req_v2(
+            "POST",
+            &url,
+            Some("application/json"),
+            &headers,
+            Some(body),
+        ));
+        assert!(verify_callback(
+            &signer,
+            &v2,
+            "POST",
+            &url,
+            Some("application/json"),
+            &headers,
+            body,
+        ));
+        assert!(!verify_callback(
+            &signer,
+            &v2,
+            "POST",
+            &url,
+            Some("application/json"),
+            &[("X-Qiniu-Foo", "baz")],
+            body,
+        ));
+
+        assert!(!verify_callback(
+            &signer,
+            "UpToken xxx",
+            "POST",
+            &url,
+            None,
+            &[],
+            body,
+        ));
+    }
+}
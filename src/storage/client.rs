@@ -1,23 +1,66 @@
 use std::borrow::Cow;
 
+use base64;
+
+#[cfg(feature = "async-api")]
+use futures::{future, stream};
 #[cfg(feature = "async-api")]
 use futures::prelude::*;
+use serde;
+use url::percent_encoding;
 
 use super::super::errors::*;
 use super::super::provider;
 use super::super::request;
 use super::super::reqwest_compat as reqwest;
+use super::download;
+use super::tokens;
 use super::types;
 
 
 pub struct QiniuStorageClient<'a> {
-    provider: &'a provider::QiniuClient,
+    pub(super) provider: &'a provider::QiniuClient,
+    hosts: provider::QiniuHosts,
 }
 
 
 impl<'a> QiniuStorageClient<'a> {
     pub fn new(provider: &'a provider::QiniuClient) -> QiniuStorageClient<'a> {
-        QiniuStorageClient { provider: provider }
+        let hosts = provider.hosts().clone();
+        QiniuStorageClient::with_hosts(provider, hosts)
+    }
+
+    /// Create a client talking to the given hosts instead of those of the
+    /// provider, e.g. the hosts of the region a bucket is in.
+    pub fn with_hosts(provider: &'a provider::QiniuClient, hosts: provider::QiniuHosts) -> QiniuStorageClient<'a> {
+        QiniuStorageClient {
+            provider: provider,
+            hosts: hosts,
+        }
+    }
+
+    /// Create a client talking to the hosts of the region the bucket is in,
+    /// discovering them if not already cached.
+    #[cfg(feature = "async-api")]
+    pub fn for_bucket(
+        provider: &'a provider::QiniuClient,
+        bucket: &str,
+    ) -> impl Future<Item = QiniuStorageClient<'a>, Error = Error> + 'a {
+        provider.bucket_hosts(bucket).map(move |hosts| {
+            QiniuStorageClient::with_hosts(provider, hosts)
+        })
+    }
+
+    /// Create a client talking to the hosts of the region the bucket is in,
+    /// discovering them if not already cached.
+    #[cfg(feature = "sync-api")]
+    pub fn for_bucket(provider: &'a provider::QiniuClient, bucket: &str) -> Result<QiniuStorageClient<'a>> {
+        let hosts = provider.bucket_hosts(bucket)?;
+        Ok(QiniuStorageClient::with_hosts(provider, hosts))
+    }
+
+    pub fn hosts(&self) -> &provider::QiniuHosts {
+        &self.hosts
     }
 
     // Flavor-agnostic APIs.
@@ -25,32 +68,150 @@ impl<'a> QiniuStorageClient<'a> {
     pub fn upload_token(&self, put_policy: types::PutPolicy) -> String {
         put_policy.into_upload_token(self.provider.signer())
     }
+
+    /// Parse the upload token, returning an error if it is not signed with
+    /// the credentials of this client.
+    ///
+    /// Expiry is not checked, see [UploadToken::is_expired].
+    ///
+    /// [UploadToken::is_expired]: ./struct.UploadToken.html#method.is_expired
+    pub fn verify_upload_token(&self, token: &str) -> Result<tokens::UploadToken> {
+        let token = tokens::UploadToken::parse(token)?;
+        if !token.verify(self.provider.signer()) {
+            bail!("upload token signature mismatch");
+        }
+
+        Ok(token)
+    }
+
+    /// Sign an arbitrary download URL for private buckets, valid until the
+    /// given Unix timestamp.
+    ///
+    /// The URL must already be properly escaped, and may contain query
+    /// strings such as data processing commands.
+    pub fn sign_download_url(&self, url: &str, deadline: u32) -> String {
+        tokens::sign_download_url(self.provider.signer(), url, deadline)
+    }
+
+    /// Signed download URL of the object in a private bucket, valid until the
+    /// given Unix timestamp.
+    ///
+    /// See [DownloadUrlBuilder] for HTTPS and data processing support.
+    ///
+    /// [DownloadUrlBuilder]: ./struct.DownloadUrlBuilder.html
+    pub fn private_download_url(&self, domain: &BucketDomain, key: &str, deadline: u32) -> String {
+        download::DownloadUrlBuilder::new(domain, key).build_private(self, deadline)
+    }
+}
+
+
+/// URL-safe Base64 encoding, as used for path segments throughout the APIs.
+pub(super) fn urlsafe_base64<T: AsRef<[u8]>>(x: T) -> String {
+    base64::encode_config(x.as_ref(), base64::URL_SAFE)
+}
+
+
+/// [EncodedEntryURI][entry-uri] of the object.
+///
+/// [entry-uri]: https://developer.qiniu.com/kodo/api/1276/data-format
+pub(super) fn encoded_entry_uri(bucket: &str, key: &str) -> String {
+    let mut tmp = bucket.to_owned();
+    tmp.push(':');
+    tmp.push_str(key);
+    urlsafe_base64(tmp)
+}
+
+
+/// Bucket name as a path segment of the APIs.
+pub(super) fn bucket_segment(bucket: &str) -> Result<String> {
+    if bucket.is_empty() || bucket == "." || bucket == ".." {
+        bail!("invalid bucket name {:?}", bucket);
+    }
+
+    Ok(
+        percent_encoding::utf8_percent_encode(bucket, percent_encoding::PATH_SEGMENT_ENCODE_SET).to_string(),
+    )
+}
+
+
+/// Execute the request if it was successfully constructed, decoding the
+/// response body as JSON.
+#[cfg(feature = "async-api")]
+pub(super) fn execute_json<T>(
+    provider: &provider::QiniuClient,
+    req: Result<request::QiniuRequest>,
+) -> impl Future<Item = T, Error = Error>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let x = req.and_then(|req| provider.execute(req));
+    let x = future::result(x).and_then(|x| {
+        x.and_then(|mut x| x.json().map_err(|e| e.into()))
+    });
+
+    x
+}
+
+
+/// Execute the request if it was successfully constructed, decoding the
+/// response body as JSON.
+#[cfg(feature = "sync-api")]
+pub(super) fn execute_json<T>(
+    provider: &provider::QiniuClient,
+    req: Result<request::QiniuRequest>,
+) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    Ok(provider.execute(req?)?.json()?)
+}
+
+
+/// Execute the request if it was successfully constructed, discarding the
+/// response body.
+#[cfg(feature = "async-api")]
+pub(super) fn execute_empty(
+    provider: &provider::QiniuClient,
+    req: Result<request::QiniuRequest>,
+) -> impl Future<Item = (), Error = Error> {
+    let x = req.and_then(|req| provider.execute(req));
+    let x = future::result(x).and_then(|x| x.map(|_| ()));
+
+    x
+}
+
+
+/// Execute the request if it was successfully constructed, discarding the
+/// response body.
+#[cfg(feature = "sync-api")]
+pub(super) fn execute_empty(
+    provider: &provider::QiniuClient,
+    req: Result<request::QiniuRequest>,
+) -> Result<()> {
+    provider.execute(req?)?;
+    Ok(())
 }
 
 
 impl<'a> QiniuStorageClient<'a> {
-    fn req_list_buckets(&self) -> request::QiniuRequest {
-        request::QiniuRequest::new(
-            reqwest::Method::Get,
-            self.provider.hosts().rs().join("buckets").unwrap(),
-            None,
-        ).unwrap()
+    fn req_list_buckets(&self) -> Result<request::QiniuRequest> {
+        let url = self.hosts.rs().join("buckets")?;
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Get, url, None)?
+                .with_endpoints(self.hosts.endpoints(provider::Service::Rs)),
+        )
     }
 
     #[cfg(feature = "async-api")]
     pub fn list_buckets(&self) -> impl Future<Item = Vec<String>, Error = Error> {
         let req = self.req_list_buckets();
-        // TODO: fix this unwrap
-        let x = self.provider.execute(req).unwrap();
-        let x = x.and_then(|mut x| x.json()).map_err(|e| e.into());
-
-        x
+        execute_json(self.provider, req)
     }
 
     #[cfg(feature = "sync-api")]
     pub fn list_buckets(&self) -> Result<Vec<String>> {
         let req = self.req_list_buckets();
-        Ok(self.provider.execute(req)?.json()?)
+        execute_json(self.provider, req)
     }
 }
 
@@ -118,9 +279,9 @@ impl ::std::ops::Deref for BucketDomain {
 
 
 impl<'a> QiniuStorageClient<'a> {
-    fn req_bucket_domains<'b: 'a>(&'a self, bucket: Cow<'b, str>) -> request::QiniuRequest {
+    fn req_bucket_domains<'b: 'a>(&'a self, bucket: Cow<'b, str>) -> Result<request::QiniuRequest> {
         let url = {
-            let mut tmp = self.provider.hosts().api().join("v6/domain/list").unwrap();
+            let mut tmp = self.hosts.api().join("v6/domain/list")?;
             {
                 let mut qs = tmp.query_pairs_mut();
                 qs.append_pair("tbl", bucket.as_ref());
@@ -128,7 +289,10 @@ impl<'a> QiniuStorageClient<'a> {
             tmp
         };
 
-        request::QiniuRequest::new(reqwest::Method::Get, url, None).unwrap()
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Get, url, None)?
+                .with_endpoints(self.hosts.endpoints(provider::Service::Api)),
+        )
     }
 
     #[cfg(feature = "async-api")]
@@ -137,21 +301,15 @@ impl<'a> QiniuStorageClient<'a> {
         bucket: Cow<'b, str>,
     ) -> impl Future<Item = Vec<BucketDomain>, Error = Error> {
         let req = self.req_bucket_domains(bucket);
-        // TODO
-        let x = self.provider.execute(req).unwrap();
-        let x = x.and_then(|mut x| {
-            x.json().map(|l: Vec<String>| {
-                l.into_iter().map(|d| d.into()).collect()
-            })
-        }).map_err(|e| e.into());
-
-        x
+        execute_json(self.provider, req).map(|l: Vec<String>| {
+            l.into_iter().map(|d| d.into()).collect()
+        })
     }
 
     #[cfg(feature = "sync-api")]
     pub fn bucket_domains<'b: 'a>(&'a self, bucket: Cow<'b, str>) -> Result<Vec<BucketDomain>> {
         let req = self.req_bucket_domains(bucket);
-        let resp: Vec<String> = self.provider.execute(req)?.json()?;
+        let resp: Vec<String> = execute_json(self.provider, req)?;
         Ok(resp.into_iter().map(|d| d.into()).collect())
     }
 }
@@ -175,72 +333,397 @@ pub struct ListBucketEntry {
     pub hash: String,
     pub mime_type: String,
     #[serde(rename = "type")]
-    pub type_: usize,
+    pub type_: types::StorageKind,
     pub end_user: Option<String>,
 }
 
 
+fn req_bucket_list(
+    hosts: &provider::QiniuHosts,
+    bucket: &str,
+    limit: Option<usize>,
+    prefix: Option<&str>,
+    delimiter: Option<&str>,
+    marker: Option<&str>,
+) -> Result<request::QiniuRequest> {
+    let url = {
+        let mut tmp = hosts.rsf().join("list")?;
+        {
+            let mut qs = tmp.query_pairs_mut();
+
+            qs.append_pair("bucket", bucket);
+            if let Some(limit) = limit {
+                qs.append_pair("limit", &format!("{}", limit));
+            }
+            if let Some(prefix) = prefix {
+                qs.append_pair("prefix", prefix);
+            }
+            if let Some(delimiter) = delimiter {
+                qs.append_pair("delimiter", delimiter);
+            }
+            if let Some(marker) = marker {
+                qs.append_pair("marker", marker);
+            }
+        }
+        tmp
+    };
+
+    Ok(
+        request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+            .with_endpoints(hosts.endpoints(provider::Service::Rsf))
+            .with_idempotent(true),
+    )
+}
+
+
 impl<'a> QiniuStorageClient<'a> {
-    fn req_bucket_list<'b: 'a>(
+    #[cfg(feature = "async-api")]
+    pub fn bucket_list<'b: 'a>(
         &'a self,
         bucket: Cow<'b, str>,
         limit: Option<usize>,
         prefix: Option<&'b str>,
         delimiter: Option<&'b str>,
         marker: Option<&'b str>,
-    ) -> request::QiniuRequest {
-        let url = {
-            let mut tmp = self.provider.hosts().rsf().join("list").unwrap();
-            {
-                let mut qs = tmp.query_pairs_mut();
+    ) -> impl Future<Item = ListResponse, Error = Error> {
+        let req = req_bucket_list(&self.hosts, &bucket, limit, prefix, delimiter, marker);
+        execute_json(self.provider, req)
+    }
 
-                qs.append_pair("bucket", bucket.as_ref());
-                if let Some(limit) = limit {
-                    qs.append_pair("limit", &format!("{}", limit));
-                }
-                if let Some(prefix) = prefix {
-                    qs.append_pair("prefix", prefix);
-                }
-                if let Some(delimiter) = delimiter {
-                    qs.append_pair("delimiter", delimiter);
+    #[cfg(feature = "sync-api")]
+    pub fn bucket_list<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        limit: Option<usize>,
+        prefix: Option<&'b str>,
+        delimiter: Option<&'b str>,
+        marker: Option<&'b str>,
+    ) -> Result<ListResponse> {
+        let req = req_bucket_list(&self.hosts, &bucket, limit, prefix, delimiter, marker);
+        execute_json(self.provider, req)
+    }
+}
+
+
+/// Item yielded when listing across all pages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListItem {
+    /// An object.
+    Entry(ListBucketEntry),
+    /// A common prefix, only returned when listing with a delimiter.
+    CommonPrefix(String),
+}
+
+
+/// State of listing across pages.
+struct ListState {
+    hosts: provider::QiniuHosts,
+    bucket: String,
+    limit: Option<usize>,
+    prefix: Option<String>,
+    delimiter: Option<String>,
+    marker: Option<String>,
+    /// Marker the current page was fetched with.
+    page_marker: Option<String>,
+    done: bool,
+}
+
+
+impl ListState {
+    fn req(&self) -> Result<request::QiniuRequest> {
+        req_bucket_list(
+            &self.hosts,
+            &self.bucket,
+            self.limit,
+            self.prefix.as_ref().map(|x| x.as_str()),
+            self.delimiter.as_ref().map(|x| x.as_str()),
+            self.marker.as_ref().map(|x| x.as_str()),
+        )
+    }
+
+    /// Advance to the next page, returning the items of the current page.
+    fn advance(&mut self, resp: ListResponse) -> Vec<ListItem> {
+        self.page_marker = self.marker.take();
+        self.marker = match resp.marker {
+            Some(ref m) if !m.is_empty() => Some(m.clone()),
+            _ => None,
+        };
+        self.done = self.marker.is_none();
+
+        let mut items: Vec<ListItem> = resp.items.into_iter().map(ListItem::Entry).collect();
+        if let Some(prefixes) = resp.common_prefixes {
+            items.extend(prefixes.into_iter().map(ListItem::CommonPrefix));
+        }
+
+        items
+    }
+
+    /// Returns the marker to resume listing from without skipping any item
+    /// not yet yielded, given if items of the current page are still
+    /// buffered.
+    ///
+    /// Markers are opaque and only exist for page boundaries, so this is the
+    /// start of the current page while any of its items are buffered.
+    fn resume_marker(&self, buffered: bool) -> Option<&str> {
+        if buffered {
+            self.page_marker.as_ref().map(|x| x.as_str())
+        } else {
+            self.marker.as_ref().map(|x| x.as_str())
+        }
+    }
+}
+
+
+/// Iterator over all items of a bucket, fetching pages as needed.
+///
+/// Obtain one with [QiniuStorageClient::bucket_list_all].
+///
+/// [QiniuStorageClient::bucket_list_all]: ./struct.QiniuStorageClient.html#method.bucket_list_all
+#[cfg(feature = "sync-api")]
+pub struct ListIter<'a> {
+    provider: &'a provider::QiniuClient,
+    state: ListState,
+    buffer: ::std::collections::VecDeque<ListItem>,
+    failed: bool,
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> ListIter<'a> {
+    /// Returns the marker to resume listing from without skipping any item
+    /// not yet yielded, or `None` to resume from the start.
+    ///
+    /// Markers only exist for page boundaries, so items of the current page
+    /// that were already yielded are yielded again after resuming. After
+    /// listing [failed], this is the start of the page that failed. The
+    /// marker is meaningless once [is_done] returns true.
+    ///
+    /// [failed]: #method.is_failed
+    /// [is_done]: #method.is_done
+    pub fn marker(&self) -> Option<&str> {
+        self.state.resume_marker(!self.buffer.is_empty())
+    }
+
+    /// Returns if all items have been yielded.
+    pub fn is_done(&self) -> bool {
+        self.buffer.is_empty() && self.state.done
+    }
+
+    /// Returns if fetching a page failed, which ends the iteration before
+    /// all items have been yielded.
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> Iterator for ListIter<'a> {
+    type Item = Result<ListItem>;
+
+    fn next(&mut self) -> Option<Result<ListItem>> {
+        while self.buffer.is_empty() {
+            if self.state.done || self.failed {
+                return None;
+            }
+
+            let req = self.state.req();
+            match execute_json(self.provider, req) {
+                Ok(resp) => {
+                    let items = self.state.advance(resp);
+                    self.buffer.extend(items);
                 }
-                if let Some(marker) = marker {
-                    qs.append_pair("marker", marker);
+                Err(e) => {
+                    // don't retry the same page forever
+                    self.failed = true;
+                    return Some(Err(e));
                 }
             }
-            tmp
-        };
+        }
+
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
 
-        request::QiniuRequest::new(reqwest::Method::Post, url, None).unwrap()
+impl<'a> QiniuStorageClient<'a> {
+    fn list_state(
+        &self,
+        bucket: &str,
+        limit: Option<usize>,
+        prefix: Option<&str>,
+        delimiter: Option<&str>,
+        marker: Option<&str>,
+    ) -> ListState {
+        ListState {
+            hosts: self.hosts.clone(),
+            bucket: bucket.to_owned(),
+            limit: limit,
+            prefix: prefix.map(|x| x.to_owned()),
+            delimiter: delimiter.map(|x| x.to_owned()),
+            marker: marker.map(|x| x.to_owned()),
+            page_marker: None,
+            done: false,
+        }
     }
 
+    /// List all items in the bucket across pages, starting from `marker` if
+    /// given. `limit` is the page size.
     #[cfg(feature = "async-api")]
-    pub fn bucket_list<'b: 'a>(
-        &'a self,
+    pub fn bucket_list_all<'b>(
+        &self,
         bucket: Cow<'b, str>,
         limit: Option<usize>,
         prefix: Option<&'b str>,
         delimiter: Option<&'b str>,
         marker: Option<&'b str>,
-    ) -> impl Future<Item = ListResponse, Error = Error> {
-        let req = self.req_bucket_list(bucket, limit, prefix, delimiter, marker);
-        // TODO: fix this unwrap
-        let x = self.provider.execute(req).unwrap();
-        let x = x.and_then(|mut x| x.json()).map_err(|e| e.into());
+    ) -> impl Stream<Item = ListItem, Error = Error> + 'a {
+        let provider = self.provider;
+        let state = self.list_state(&bucket, limit, prefix, delimiter, marker);
+
+        let x = stream::unfold(state, move |mut state| {
+            if state.done {
+                return None;
+            }
+
+            let req = state.req();
+            Some(execute_json(provider, req).map(move |resp| {
+                let items = state.advance(resp);
+                (items, state)
+            }))
+        });
 
-        x
+        x.map(stream::iter_ok).flatten()
     }
 
+    /// List all items in the bucket across pages, starting from `marker` if
+    /// given. `limit` is the page size.
     #[cfg(feature = "sync-api")]
-    pub fn bucket_list<'b: 'a>(
-        &'a self,
+    pub fn bucket_list_all<'b>(
+        &self,
         bucket: Cow<'b, str>,
         limit: Option<usize>,
         prefix: Option<&'b str>,
         delimiter: Option<&'b str>,
         marker: Option<&'b str>,
-    ) -> Result<ListResponse> {
-        let req = self.req_bucket_list(bucket, limit, prefix, delimiter, marker);
-        Ok(self.provider.execute(req)?.json()?)
+    ) -> ListIter<'a> {
+        ListIter {
+            provider: self.provider,
+            state: self.list_state(&bucket, limit, prefix, delimiter, marker),
+            buffer: ::std::collections::VecDeque::new(),
+            failed: false,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "async-api")]
+    use tokio_core::reactor;
+
+    use super::*;
+
+
+    fn with_provider<F: FnOnce(&provider::QiniuClient)>(f: F) {
+        #[cfg(feature = "async-api")]
+        {
+            let core = reactor::Core::new().unwrap();
+            f(&provider::QiniuClient::new(&core.handle(), "ak", "sk"));
+        }
+
+        #[cfg(feature = "sync-api")]
+        {
+            f(&provider::QiniuClient::new("ak", "sk"));
+        }
+    }
+
+    #[test]
+    fn test_req_weird_bucket_names() {
+        with_provider(|p| {
+            let c = QiniuStorageClient::new(p);
+            for bucket in &["", "bad bucket", "a/b?c#d", "%zz", "\u{0}", "存储空间"] {
+                assert!(c.req_bucket_domains(Cow::Borrowed(bucket)).is_ok());
+                assert!(req_bucket_list(c.hosts(), bucket, Some(0), Some(""), Some("/"), Some("?")).is_ok());
+            }
+        });
+    }
+
+    fn list_page(marker: &str, keys: &[&str], prefixes: &[&str]) -> ListResponse {
+        ListResponse {
+            marker: Some(marker.to_owned()),
+            common_prefixes: Some(prefixes.iter().map(|x| x.to_string()).collect()),
+            items: keys.iter()
+                .map(|k| {
+                    let entry = format!(
+                        r#"{{"key":"{}","putTime":1,"fsize":1,"hash":"h","mimeType":"text/plain","type":0}}"#,
+                        k
+                    );
+                    ::serde_json::from_str(&entry).unwrap()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_list_state() {
+        with_provider(|p| {
+            let c = QiniuStorageClient::new(p);
+            let mut state = c.list_state("b", None, None, Some("/"), Some("m0"));
+            assert_eq!(state.resume_marker(false), Some("m0"));
+
+            let items = state.advance(list_page("m1", &["a", "b"], &["c/"]));
+            assert_eq!(items.len(), 3);
+            assert_eq!(items[2], ListItem::CommonPrefix("c/".to_owned()));
+            match items[0] {
+                ListItem::Entry(ref e) => assert_eq!(e.key, "a"),
+                _ => panic!("expected an entry"),
+            }
+            assert!(!state.done);
+            // items of the page still buffered, so resume from its start
+            assert_eq!(state.resume_marker(true), Some("m0"));
+            assert_eq!(state.resume_marker(false), Some("m1"));
+
+            let items = state.advance(list_page("", &["d"], &[]));
+            assert_eq!(items.len(), 1);
+            assert!(state.done);
+            assert_eq!(state.resume_marker(true), Some("m1"));
+            assert_eq!(state.resume_marker(false), None);
+        });
+    }
+
+    #[test]
+    fn test_list_bucket_entry_type() {
+        let entry = |type_: u64| -> ListBucketEntry {
+            ::serde_json::from_str(&format!(
+                r#"{{"key":"a","putTime":1,"fsize":2,"hash":"h","mimeType":"text/plain","type":{}}}"#,
+                type_
+            )).unwrap()
+        };
+
+        assert_eq!(entry(2).type_, types::StorageKind::Archive);
+        assert_eq!(entry(9).type_, types::StorageKind::Unknown(9));
+        assert_eq!(entry(9).end_user, None);
+    }
+
+    #[test]
+    fn test_bucket_segment() {
+        assert_eq!(bucket_segment("my-bucket").unwrap(), "my-bucket");
+        assert_eq!(bucket_segment("a/b?c#d").unwrap(), "a%2Fb%3Fc%23d");
+        assert!(bucket_segment("").is_err());
+        assert!(bucket_segment("..").is_err());
+    }
+
+    #[test]
+    fn test_failed_request_construction() {
+        with_provider(|p| {
+            let req = request::QiniuRequest::new(reqwest::Method::Get, "not a url", None);
+            assert!(req.is_err());
+
+            let x = execute_json::<Vec<String>>(p, req);
+            #[cfg(feature = "async-api")]
+            let x = x.wait();
+            assert!(x.is_err());
+        });
     }
 }
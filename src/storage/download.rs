@@ -0,0 +1,147 @@
+//! [Download URLs][download] of objects.
+//!
+//! [download]: https://developer.qiniu.com/kodo/manual/1232/download-process
+
+use url;
+
+use super::client::{BucketDomain, QiniuStorageClient};
+
+
+/// Percent-encode the object key for use in URL paths.
+///
+/// Slashes are kept as-is, as they are commonly used as "directory"
+/// separators in keys.
+fn escape_key(key: &str) -> String {
+    let mut tmp = String::with_capacity(key.len());
+    for &b in key.as_bytes() {
+        match b {
+            b'A'...b'Z' | b'a'...b'z' | b'0'...b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                tmp.push(b as char)
+            }
+            _ => tmp.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    tmp
+}
+
+
+/// Builder of download URLs.
+///
+/// ```ignore
+/// let url = DownloadUrlBuilder::new(&domain, "photos/sun flower.jpg")
+///     .https(true)
+///     .fop("imageView2/1/w/100")
+///     .attname("flower.jpg")
+///     .build();
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DownloadUrlBuilder {
+    domain: String,
+    key: String,
+    https: bool,
+    fops: Vec<String>,
+    attname: Option<String>,
+}
+
+
+impl DownloadUrlBuilder {
+    /// Begin constructing the URL of the object under the domain. Plain HTTP
+    /// is used unless changed with [https].
+    ///
+    /// [https]: #method.https
+    pub fn new(domain: &BucketDomain, key: &str) -> Self {
+        DownloadUrlBuilder {
+            domain: String::from(&**domain),
+            key: key.to_owned(),
+            https: false,
+            fops: Vec::new(),
+            attname: None,
+        }
+    }
+
+    /// Use HTTPS instead of HTTP.
+    pub fn https(mut self, https: bool) -> Self {
+        self.https = https;
+        self
+    }
+
+    /// Append a [data processing][fop] command to the pipeline, e.g.
+    /// `imageView2/1/w/100`. Multiple commands are chained with `|`.
+    ///
+    /// [fop]: https://developer.qiniu.com/dora/manual/1204/processing-mechanism
+    pub fn fop(mut self, fop: &str) -> Self {
+        self.fops.push(fop.to_owned());
+        self
+    }
+
+    /// Set the file name browsers should save the download as.
+    pub fn attname(mut self, attname: &str) -> Self {
+        self.attname = Some(attname.to_owned());
+        self
+    }
+
+    /// Return the public URL.
+    pub fn build(self) -> String {
+        let mut tmp = String::from(if self.https { "https://" } else { "http://" });
+        tmp.push_str(&self.domain);
+        tmp.push('/');
+        tmp.push_str(&escape_key(&self.key));
+
+        if !self.fops.is_empty() {
+            tmp.push('?');
+            tmp.push_str(&self.fops.join("|"));
+        }
+
+        if let Some(attname) = self.attname {
+            tmp.push(if self.fops.is_empty() { '?' } else { '&' });
+            tmp.push_str("attname=");
+            tmp.extend(url::form_urlencoded::byte_serialize(attname.as_bytes()));
+        }
+
+        tmp
+    }
+
+    /// Return the URL signed for private buckets, valid until the given Unix
+    /// timestamp.
+    pub fn build_private(self, client: &QiniuStorageClient, deadline: u32) -> String {
+        client.sign_download_url(&self.build(), deadline)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_escape_key() {
+        assert_eq!(escape_key("resource/my flower.jpg"), "resource/my%20flower.jpg");
+        assert_eq!(escape_key("a?b#c%d+e/f~"), "a%3Fb%23c%25d%2Be/f~");
+        assert_eq!(escape_key("图片"), "%E5%9B%BE%E7%89%87");
+    }
+
+    #[test]
+    fn test_download_url_builder() {
+        let domain: BucketDomain = "78re52.com1.z0.glb.clouddn.com".to_owned().into();
+
+        assert_eq!(
+            DownloadUrlBuilder::new(&domain, "resource/flower.jpg").build(),
+            "http://78re52.com1.z0.glb.clouddn.com/resource/flower.jpg"
+        );
+        assert_eq!(
+            DownloadUrlBuilder::new(&domain, "a b.jpg")
+                .https(true)
+                .fop("imageView2/1/w/100")
+                .fop("watermark/2/text/5LiD54mb")
+                .attname("花 1.jpg")
+                .build(),
+            "https://78re52.com1.z0.glb.clouddn.com/a%20b.jpg?imageView2/1/w/100|watermark/2/text/5LiD54mb&attname=%E8%8A%B1+1.jpg"
+        );
+        assert_eq!(
+            DownloadUrlBuilder::new(&domain, "x").attname("y").build(),
+            "http://78re52.com1.z0.glb.clouddn.com/x?attname=y"
+        );
+    }
+}
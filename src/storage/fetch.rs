@@ -0,0 +1,275 @@
+//! Fetching of remote URLs into buckets, either [synchronously][fetch] or
+//! as [asynchronous jobs][async-fetch].
+//!
+//! [fetch]: https://developer.qiniu.com/kodo/api/1263/fetch
+//! [async-fetch]: https://developer.qiniu.com/kodo/api/4097/asynch-fetch
+
+use std::borrow::Cow;
+
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+use serde_json;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+use super::types::StorageKind;
+
+
+/// Metadata of an object fetched synchronously.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FetchResponse {
+    pub key: Option<String>,
+    pub hash: String,
+    pub fsize: u64,
+    pub mime_type: String,
+}
+
+
+/// Optional parameters of asynchronous fetch jobs.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct AsyncFetchOptions {
+    /// `Host` header to send when fetching the URL.
+    pub host: Option<String>,
+    /// Expected MD5 of the content in hex, the job fails on mismatch.
+    pub md5: Option<String>,
+    /// Expected [etag][qetag] of the content, the job fails on mismatch.
+    ///
+    /// [qetag]: ../qetag/index.html
+    pub etag: Option<String>,
+    /// URL notified after the job finishes, with the same semantics as
+    /// upload callbacks.
+    pub callback_url: Option<String>,
+    pub callback_body: Option<String>,
+    pub callback_body_type: Option<String>,
+    pub callback_host: Option<String>,
+    /// Storage kind of the fetched object.
+    pub file_type: Option<StorageKind>,
+    /// Skip the job if the key already exists, instead of overwriting.
+    pub ignore_same_key: bool,
+}
+
+
+#[derive(Serialize)]
+struct AsyncFetchBody<'a> {
+    url: &'a str,
+    bucket: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    md5: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag: Option<&'a str>,
+    #[serde(rename = "callbackurl", skip_serializing_if = "Option::is_none")]
+    callback_url: Option<&'a str>,
+    #[serde(rename = "callbackbody", skip_serializing_if = "Option::is_none")]
+    callback_body: Option<&'a str>,
+    #[serde(rename = "callbackbodytype", skip_serializing_if = "Option::is_none")]
+    callback_body_type: Option<&'a str>,
+    #[serde(rename = "callbackhost", skip_serializing_if = "Option::is_none")]
+    callback_host: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_type: Option<StorageKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ignore_same_key: Option<bool>,
+}
+
+
+impl<'a> AsyncFetchBody<'a> {
+    fn new(url: &'a str, bucket: &'a str, key: Option<&'a str>, options: &'a AsyncFetchOptions) -> Self {
+        fn s(x: &Option<String>) -> Option<&str> {
+            x.as_ref().map(|x| x.as_str())
+        }
+
+        AsyncFetchBody {
+            url: url,
+            bucket: bucket,
+            key: key,
+            host: s(&options.host),
+            md5: s(&options.md5),
+            etag: s(&options.etag),
+            callback_url: s(&options.callback_url),
+            callback_body: s(&options.callback_body),
+            callback_body_type: s(&options.callback_body_type),
+            callback_host: s(&options.callback_host),
+            file_type: options.file_type,
+            ignore_same_key: if options.ignore_same_key { Some(true) } else { None },
+        }
+    }
+}
+
+
+/// Status of an asynchronous fetch job.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AsyncFetchStatus {
+    /// Job ID.
+    pub id: String,
+    /// Number of jobs queued before this one, 0 if the job is being
+    /// processed, or -1 if it has been processed at least once.
+    pub wait: i64,
+}
+
+
+impl AsyncFetchStatus {
+    /// Returns if the job has been processed at least once.
+    pub fn is_processed(&self) -> bool {
+        self.wait < 0
+    }
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    fn req_fetch<'b: 'a>(&'a self, url: &str, bucket: Cow<'b, str>, key: Cow<'b, str>) -> Result<request::QiniuRequest> {
+        let path = format!(
+            "fetch/{}/to/{}",
+            client::urlsafe_base64(url),
+            client::encoded_entry_uri(&bucket, &key)
+        );
+        let url = self.hosts().io().join(&path)?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Io)),
+        )
+    }
+
+    fn req_async_fetch(
+        &self,
+        url: &str,
+        bucket: &str,
+        key: Option<&str>,
+        options: &AsyncFetchOptions,
+    ) -> Result<request::QiniuRequest> {
+        let body = serde_json::to_vec(&AsyncFetchBody::new(url, bucket, key, options))?;
+        let url = self.hosts().api().join("sisyphus/fetch")?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, Some(body.into()))?
+                .with_content_type("application/json")
+                .with_auth(request::Authorization::Qiniu)
+                .with_endpoints(self.hosts().endpoints(provider::Service::Api)),
+        )
+    }
+
+    fn req_async_fetch_status(&self, id: &str) -> Result<request::QiniuRequest> {
+        let url = {
+            let mut tmp = self.hosts().api().join("sisyphus/fetch")?;
+            tmp.query_pairs_mut().append_pair("id", id);
+            tmp
+        };
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Get, url, None)?
+                .with_auth(request::Authorization::Qiniu)
+                .with_endpoints(self.hosts().endpoints(provider::Service::Api)),
+        )
+    }
+}
+
+
+#[cfg(feature = "async-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Fetch the URL into the bucket under the key, waiting for completion.
+    ///
+    /// Only suitable for small files, use [async_fetch] otherwise.
+    ///
+    /// [async_fetch]: #method.async_fetch
+    pub fn fetch<'b: 'a>(
+        &'a self,
+        url: &str,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+    ) -> impl Future<Item = FetchResponse, Error = Error> {
+        let req = self.req_fetch(url, bucket, key);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Create an asynchronous job fetching the URL into the bucket, under
+    /// the key if given or named after the etag otherwise.
+    pub fn async_fetch(
+        &self,
+        url: &str,
+        bucket: &str,
+        key: Option<&str>,
+        options: &AsyncFetchOptions,
+    ) -> impl Future<Item = AsyncFetchStatus, Error = Error> {
+        let req = self.req_async_fetch(url, bucket, key, options);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Query the status of the asynchronous fetch job.
+    pub fn async_fetch_status(&self, id: &str) -> impl Future<Item = AsyncFetchStatus, Error = Error> {
+        let req = self.req_async_fetch_status(id);
+        client::execute_json(self.provider, req)
+    }
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Fetch the URL into the bucket under the key, waiting for completion.
+    ///
+    /// Only suitable for small files, use [async_fetch] otherwise.
+    ///
+    /// [async_fetch]: #method.async_fetch
+    pub fn fetch<'b: 'a>(&'a self, url: &str, bucket: Cow<'b, str>, key: Cow<'b, str>) -> Result<FetchResponse> {
+        let req = self.req_fetch(url, bucket, key);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Create an asynchronous job fetching the URL into the bucket, under
+    /// the key if given or named after the etag otherwise.
+    pub fn async_fetch(
+        &self,
+        url: &str,
+        bucket: &str,
+        key: Option<&str>,
+        options: &AsyncFetchOptions,
+    ) -> Result<AsyncFetchStatus> {
+        let req = self.req_async_fetch(url, bucket, key, options);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Query the status of the asynchronous fetch job.
+    pub fn async_fetch_status(&self, id: &str) -> Result<AsyncFetchStatus> {
+        let req = self.req_async_fetch_status(id);
+        client::execute_json(self.provider, req)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_async_fetch_body() {
+        let options = AsyncFetchOptions {
+            md5: Some("d41d8cd98f00b204e9800998ecf8427e".to_owned()),
+            callback_url: Some("https://app.example.com/cb".to_owned()),
+            file_type: Some(StorageKind::LowFrequency),
+            ignore_same_key: true,
+            ..Default::default()
+        };
+        let body = AsyncFetchBody::new("https://example.com/a.jpg", "b", None, &options);
+
+        assert_eq!(
+            serde_json::to_string(&body).unwrap(),
+            concat!(
+                r#"{"url":"https://example.com/a.jpg","bucket":"b","#,
+                r#""md5":"d41d8cd98f00b204e9800998ecf8427e","callbackurl":"https://app.example.com/cb","#,
+                r#""file_type":1,"ignore_same_key":true}"#
+            )
+        );
+
+        let status: AsyncFetchStatus = serde_json::from_str(r#"{"id":"eyJ6b25l","wait":-1}"#).unwrap();
+        assert!(status.is_processed());
+    }
+}
@@ -0,0 +1,432 @@
+//! [Lifecycle rules][lifecycle] of buckets, transitioning objects to
+//! cheaper storage kinds and deleting them after some days.
+//!
+//! [lifecycle]: https://developer.qiniu.com/kodo/manual/3699/life-cycle-management
+
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+use url;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+use super::types::StorageKind;
+
+
+/// Lifecycle rule of a bucket, applying to objects with the key prefix.
+///
+/// The APIs identify rules by `name`, and several rules may share a prefix.
+/// The `*_for_prefix` methods of the client look rules up by `prefix`
+/// instead, failing if it is ambiguous.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifecycleRule {
+    /// Name of the rule, unique in the bucket.
+    pub name: String,
+    /// Key prefix of the objects the rule applies to, empty for all objects.
+    pub prefix: String,
+    /// Days after upload to transition objects to each storage kind.
+    pub transitions: Vec<(StorageKind, u32)>,
+    /// Days after upload to delete objects.
+    pub delete_after_days: Option<u32>,
+}
+
+
+impl LifecycleRule {
+    /// Creates a rule doing nothing yet.
+    pub fn new<S: Into<String>, T: Into<String>>(name: S, prefix: T) -> LifecycleRule {
+        LifecycleRule {
+            name: name.into(),
+            prefix: prefix.into(),
+            transitions: Vec::new(),
+            delete_after_days: None,
+        }
+    }
+
+    /// Transition objects to the storage kind after the days, replacing any
+    /// previous transition to the same kind.
+    pub fn transition(mut self, kind: StorageKind, days: u32) -> Self {
+        self.transitions.retain(|x| x.0 != kind);
+        self.transitions.push((kind, days));
+        self
+    }
+
+    /// Delete objects after the days.
+    pub fn delete_after_days(mut self, days: u32) -> Self {
+        self.delete_after_days = Some(days);
+        self
+    }
+
+    /// Returns the days after which objects transition to the storage kind,
+    /// if they do.
+    pub fn transition_days(&self, kind: StorageKind) -> Option<u32> {
+        self.transitions.iter().find(|x| x.0 == kind).map(|x| x.1)
+    }
+
+    fn to_form(&self, bucket: &str) -> Result<String> {
+        for &(kind, _) in &self.transitions {
+            if !TRANSITION_FIELDS.iter().any(|x| x.0 == kind) {
+                bail!("cannot transition objects to {:?}", kind);
+            }
+        }
+
+        let mut tmp = url::form_urlencoded::Serializer::new(String::new());
+        tmp.append_pair("bucket", bucket);
+        tmp.append_pair("name", &self.name);
+        tmp.append_pair("prefix", &self.prefix);
+        tmp.append_pair(
+            "delete_after_days",
+            &self.delete_after_days.unwrap_or(0).to_string(),
+        );
+
+        for &(kind, field) in TRANSITION_FIELDS {
+            let days = self.transition_days(kind).unwrap_or(0);
+            tmp.append_pair(field, &days.to_string());
+        }
+
+        Ok(tmp.finish())
+    }
+}
+
+
+/// Storage kinds objects can transition to, and their API fields.
+const TRANSITION_FIELDS: &'static [(StorageKind, &'static str)] = &[
+    (StorageKind::LowFrequency, "to_line_after_days"),
+    (StorageKind::Archive, "to_archive_after_days"),
+    (StorageKind::DeepArchive, "to_deep_archive_after_days"),
+];
+
+
+/// Lifecycle rule as returned by the `rules/get` API, where 0 days means
+/// disabled.
+#[derive(Deserialize)]
+struct RawLifecycleRule {
+    name: String,
+    prefix: Option<String>,
+    delete_after_days: Option<u32>,
+    to_line_after_days: Option<u32>,
+    to_archive_after_days: Option<u32>,
+    to_deep_archive_after_days: Option<u32>,
+}
+
+
+impl From<RawLifecycleRule> for LifecycleRule {
+    fn from(raw: RawLifecycleRule) -> LifecycleRule {
+        fn days(x: Option<u32>) -> Option<u32> {
+            x.and_then(|x| if x == 0 { None } else { Some(x) })
+        }
+
+        let transitions = [
+            (StorageKind::LowFrequency, raw.to_line_after_days),
+            (StorageKind::Archive, raw.to_archive_after_days),
+            (StorageKind::DeepArchive, raw.to_deep_archive_after_days),
+        ];
+
+        LifecycleRule {
+            name: raw.name,
+            prefix: raw.prefix.unwrap_or_default(),
+            transitions: transitions
+                .iter()
+                .filter_map(|&(kind, x)| days(x).map(|x| (kind, x)))
+                .collect(),
+            delete_after_days: days(raw.delete_after_days),
+        }
+    }
+}
+
+
+fn rules_of(raw: Option<Vec<RawLifecycleRule>>) -> Vec<LifecycleRule> {
+    raw.unwrap_or_default().into_iter().map(LifecycleRule::from).collect()
+}
+
+
+/// The rule with the prefix, failing if several rules have it.
+fn rule_with_prefix(rules: Vec<LifecycleRule>, prefix: &str) -> Result<Option<LifecycleRule>> {
+    let mut found = rules.into_iter().filter(|x| x.prefix == prefix);
+    let rule = found.next();
+    if found.next().is_some() {
+        bail!("several lifecycle rules with prefix {:?}", prefix);
+    }
+    Ok(rule)
+}
+
+
+/// Name of the only rule with the prefix.
+fn rule_name_with_prefix(rules: Vec<LifecycleRule>, prefix: &str) -> Result<String> {
+    match rule_with_prefix(rules, prefix)? {
+        Some(x) => Ok(x.name),
+        None => bail!("no lifecycle rule with prefix {:?}", prefix),
+    }
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    fn req_lifecycle_rules(&self, bucket: &str) -> Result<request::QiniuRequest> {
+        let url = {
+            let mut tmp = self.hosts().uc().join("rules/get")?;
+            tmp.query_pairs_mut().append_pair("bucket", bucket);
+            tmp
+        };
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Get, url, None)?
+                .with_endpoints(self.hosts().endpoints(provider::Service::Uc)),
+        )
+    }
+
+    fn req_lifecycle_rule(&self, path: &str, body: String) -> Result<request::QiniuRequest> {
+        let url = self.hosts().uc().join(path)?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, Some(body.into()))?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Uc)),
+        )
+    }
+
+    fn req_delete_lifecycle_rule(&self, bucket: &str, name: &str) -> Result<request::QiniuRequest> {
+        let body = {
+            let mut tmp = url::form_urlencoded::Serializer::new(String::new());
+            tmp.append_pair("bucket", bucket);
+            tmp.append_pair("name", name);
+            tmp.finish()
+        };
+
+        self.req_lifecycle_rule("rules/delete", body)
+    }
+
+    fn req_add_lifecycle_rule(&self, bucket: &str, rule: &LifecycleRule) -> Result<request::QiniuRequest> {
+        self.req_lifecycle_rule("rules/add", rule.to_form(bucket)?)
+    }
+
+    fn req_update_lifecycle_rule(&self, bucket: &str, rule: &LifecycleRule) -> Result<request::QiniuRequest> {
+        Ok(
+            self.req_lifecycle_rule("rules/update", rule.to_form(bucket)?)?
+                .with_idempotent(true),
+        )
+    }
+}
+
+
+#[cfg(feature = "async-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Returns the lifecycle rules of the bucket.
+    pub fn lifecycle_rules(&self, bucket: &str) -> impl Future<Item = Vec<LifecycleRule>, Error = Error> {
+        let req = self.req_lifecycle_rules(bucket);
+        client::execute_json(self.provider, req).map(rules_of)
+    }
+
+    /// Add the lifecycle rule to the bucket, failing if it already has a rule
+    /// with the same name.
+    ///
+    /// Only transitions to low-frequency, archive and deep archive storage
+    /// are supported.
+    pub fn add_lifecycle_rule(&self, bucket: &str, rule: &LifecycleRule) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_add_lifecycle_rule(bucket, rule);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Replace the lifecycle rule of the bucket with the same name, whatever
+    /// its prefix.
+    ///
+    /// Only transitions to low-frequency, archive and deep archive storage
+    /// are supported.
+    pub fn update_lifecycle_rule(
+        &self,
+        bucket: &str,
+        rule: &LifecycleRule,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_update_lifecycle_rule(bucket, rule);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Delete the lifecycle rule of the bucket with the name.
+    pub fn delete_lifecycle_rule(&self, bucket: &str, name: &str) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_delete_lifecycle_rule(bucket, name);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Returns the lifecycle rule of the bucket with the prefix, failing if
+    /// several rules have it.
+    pub fn lifecycle_rule_for_prefix(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> impl Future<Item = Option<LifecycleRule>, Error = Error> {
+        let prefix = prefix.to_owned();
+        self.lifecycle_rules(bucket).and_then(
+            move |rules| rule_with_prefix(rules, &prefix),
+        )
+    }
+
+    /// Replace the only lifecycle rule of the bucket with the prefix of the
+    /// rule, keeping its name.
+    ///
+    /// Only transitions to low-frequency, archive and deep archive storage
+    /// are supported.
+    pub fn update_lifecycle_rule_for_prefix(
+        &self,
+        bucket: &str,
+        rule: &LifecycleRule,
+    ) -> impl Future<Item = (), Error = Error> + 'a {
+        let provider = self.provider;
+        let hosts = self.hosts().clone();
+        let bucket = bucket.to_owned();
+        let rule = rule.clone();
+
+        self.lifecycle_rules(&bucket).and_then(move |rules| {
+            let this = QiniuStorageClient::with_hosts(provider, hosts);
+            let req = rule_name_with_prefix(rules, &rule.prefix).and_then(|name| {
+                this.req_update_lifecycle_rule(&bucket, &LifecycleRule { name: name, ..rule })
+            });
+            client::execute_empty(provider, req)
+        })
+    }
+
+    /// Delete the only lifecycle rule of the bucket with the prefix.
+    pub fn delete_lifecycle_rule_for_prefix(
+        &self,
+        bucket: &str,
+        prefix: &str,
+    ) -> impl Future<Item = (), Error = Error> + 'a {
+        let provider = self.provider;
+        let hosts = self.hosts().clone();
+        let bucket = bucket.to_owned();
+        let prefix = prefix.to_owned();
+
+        self.lifecycle_rules(&bucket).and_then(move |rules| {
+            let this = QiniuStorageClient::with_hosts(provider, hosts);
+            let req = rule_name_with_prefix(rules, &prefix).and_then(|name| {
+                this.req_delete_lifecycle_rule(&bucket, &name)
+            });
+            client::execute_empty(provider, req)
+        })
+    }
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Returns the lifecycle rules of the bucket.
+    pub fn lifecycle_rules(&self, bucket: &str) -> Result<Vec<LifecycleRule>> {
+        let req = self.req_lifecycle_rules(bucket);
+        Ok(rules_of(client::execute_json(self.provider, req)?))
+    }
+
+    /// Add the lifecycle rule to the bucket, failing if it already has a rule
+    /// with the same name.
+    ///
+    /// Only transitions to low-frequency, archive and deep archive storage
+    /// are supported.
+    pub fn add_lifecycle_rule(&self, bucket: &str, rule: &LifecycleRule) -> Result<()> {
+        let req = self.req_add_lifecycle_rule(bucket, rule);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Replace the lifecycle rule of the bucket with the same name, whatever
+    /// its prefix.
+    ///
+    /// Only transitions to low-frequency, archive and deep archive storage
+    /// are supported.
+    pub fn update_lifecycle_rule(&self, bucket: &str, rule: &LifecycleRule) -> Result<()> {
+        let req = self.req_update_lifecycle_rule(bucket, rule);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Delete the lifecycle rule of the bucket with the name.
+    pub fn delete_lifecycle_rule(&self, bucket: &str, name: &str) -> Result<()> {
+        let req = self.req_delete_lifecycle_rule(bucket, name);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Returns the lifecycle rule of the bucket with the prefix, failing if
+    /// several rules have it.
+    pub fn lifecycle_rule_for_prefix(&self, bucket: &str, prefix: &str) -> Result<Option<LifecycleRule>> {
+        rule_with_prefix(self.lifecycle_rules(bucket)?, prefix)
+    }
+
+    /// Replace the only lifecycle rule of the bucket with the prefix of the
+    /// rule, keeping its name.
+    ///
+    /// Only transitions to low-frequency, archive and deep archive storage
+    /// are supported.
+    pub fn update_lifecycle_rule_for_prefix(&self, bucket: &str, rule: &LifecycleRule) -> Result<()> {
+        let name = rule_name_with_prefix(self.lifecycle_rules(bucket)?, &rule.prefix)?;
+        let rule = LifecycleRule { name: name, ..rule.clone() };
+        self.update_lifecycle_rule(bucket, &rule)
+    }
+
+    /// Delete the only lifecycle rule of the bucket with the prefix.
+    pub fn delete_lifecycle_rule_for_prefix(&self, bucket: &str, prefix: &str) -> Result<()> {
+        let name = rule_name_with_prefix(self.lifecycle_rules(bucket)?, prefix)?;
+        self.delete_lifecycle_rule(bucket, &name)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+
+    #[test]
+    fn test_rule_form() {
+        let rule = LifecycleRule::new("logs", "logs/")
+            .transition(StorageKind::LowFrequency, 30)
+            .delete_after_days(365);
+
+        assert_eq!(
+            rule.to_form("b").unwrap(),
+            concat!(
+                "bucket=b&name=logs&prefix=logs%2F&delete_after_days=365&",
+                "to_line_after_days=30&to_archive_after_days=0&to_deep_archive_after_days=0"
+            )
+        );
+
+        let rule = rule.transition(StorageKind::Conventional, 1);
+        assert!(rule.to_form("b").is_err());
+    }
+
+    #[test]
+    fn test_rules_of() {
+        let raw = serde_json::from_str(concat!(
+            r#"[{"name":"logs","prefix":"logs/","delete_after_days":365,"to_line_after_days":30,"#,
+            r#""to_archive_after_days":0,"ctime":"2018-10-18T00:00:00Z"}]"#
+        )).unwrap();
+        let rules = rules_of(Some(raw));
+
+        assert_eq!(
+            rules,
+            vec![
+                LifecycleRule::new("logs", "logs/")
+                    .transition(StorageKind::LowFrequency, 30)
+                    .delete_after_days(365),
+            ]
+        );
+        assert_eq!(rules[0].transition_days(StorageKind::Archive), None);
+        assert!(rules_of(None).is_empty());
+    }
+
+    #[test]
+    fn test_rule_with_prefix() {
+        let rules = vec![
+            LifecycleRule::new("logs", "logs/"),
+            LifecycleRule::new("tmp", "tmp/"),
+            LifecycleRule::new("tmp-old", "tmp/"),
+        ];
+
+        assert_eq!(
+            rule_with_prefix(rules.clone(), "logs/").unwrap(),
+            Some(LifecycleRule::new("logs", "logs/"))
+        );
+        assert_eq!(rule_with_prefix(rules.clone(), "").unwrap(), None);
+        assert!(rule_with_prefix(rules.clone(), "tmp/").is_err());
+
+        assert_eq!(rule_name_with_prefix(rules.clone(), "logs/").unwrap(), "logs");
+        assert!(rule_name_with_prefix(rules.clone(), "").is_err());
+        assert!(rule_name_with_prefix(rules, "tmp/").is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! [Resource management][rs] of individual objects.
+//!
+//! [rs]: https://developer.qiniu.com/kodo/api/1274/rs
+
+use std::borrow::Cow;
+
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+use super::types::StorageKind;
+
+
+/// Metadata of an object, as returned by the [stat API][stat].
+///
+/// [stat]: https://developer.qiniu.com/kodo/api/1308/stat
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatResponse {
+    pub fsize: u64,
+    pub hash: String,
+    pub mime_type: String,
+    /// Upload time in units of 100 nanoseconds since the Unix epoch.
+    pub put_time: u64,
+    #[serde(rename = "type")]
+    pub type_: StorageKind,
+    pub md5: Option<String>,
+    pub end_user: Option<String>,
+    /// Unfreezing status of archived objects, 1 if in progress and 2 if
+    /// done.
+    pub restore_status: Option<u64>,
+    /// Unix timestamp of the scheduled deletion of the object, if any.
+    pub expiration: Option<u64>,
+    /// 1 if the object is disabled.
+    pub status: Option<u64>,
+}
+
+
+pub(super) fn stat_op(bucket: &str, key: &str) -> String {
+    format!("/stat/{}", client::encoded_entry_uri(bucket, key))
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    fn req_stat<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>) -> Result<request::QiniuRequest> {
+        let url = self.hosts().rs().join(&stat_op(&bucket, &key)[1..])?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Get, url, None)?
+                .with_endpoints(self.hosts().endpoints(provider::Service::Rs)),
+        )
+    }
+
+    #[cfg(feature = "async-api")]
+    pub fn stat<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+    ) -> impl Future<Item = StatResponse, Error = Error> {
+        let req = self.req_stat(bucket, key);
+        client::execute_json(self.provider, req)
+    }
+
+    #[cfg(feature = "sync-api")]
+    pub fn stat<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>) -> Result<StatResponse> {
+        let req = self.req_stat(bucket, key);
+        client::execute_json(self.provider, req)
+    }
+}
+
+
+/// Status of an object. Disabled objects cannot be downloaded.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ObjectStatus {
+    Enabled,
+    Disabled,
+}
+
+
+pub(super) fn delete_op(bucket: &str, key: &str) -> String {
+    format!("/delete/{}", client::encoded_entry_uri(bucket, key))
+}
+
+
+pub(super) fn move_op(src_bucket: &str, src_key: &str, dest_bucket: &str, dest_key: &str, force: bool) -> String {
+    format!(
+        "/move/{}/{}/force/{}",
+        client::encoded_entry_uri(src_bucket, src_key),
+        client::encoded_entry_uri(dest_bucket, dest_key),
+        force
+    )
+}
+
+
+pub(super) fn copy_op(src_bucket: &str, src_key: &str, dest_bucket: &str, dest_key: &str, force: bool) -> String {
+    format!(
+        "/copy/{}/{}/force/{}",
+        client::encoded_entry_uri(src_bucket, src_key),
+        client::encoded_entry_uri(dest_bucket, dest_key),
+        force
+    )
+}
+
+
+pub(super) fn change_mime_op(bucket: &str, key: &str, mime_type: &str) -> String {
+    format!(
+        "/chgm/{}/mime/{}",
+        client::encoded_entry_uri(bucket, key),
+        client::urlsafe_base64(mime_type)
+    )
+}
+
+
+pub(super) fn change_type_op(bucket: &str, key: &str, kind: StorageKind) -> String {
+    format!(
+        "/chtype/{}/type/{}",
+        client::encoded_entry_uri(bucket, key),
+        kind.to_u64()
+    )
+}
+
+
+pub(super) fn change_status_op(bucket: &str, key: &str, status: ObjectStatus) -> String {
+    format!(
+        "/chstatus/{}/status/{}",
+        client::encoded_entry_uri(bucket, key),
+        match status {
+            ObjectStatus::Enabled => 0,
+            ObjectStatus::Disabled => 1,
+        }
+    )
+}
+
+
+pub(super) fn delete_after_days_op(bucket: &str, key: &str, days: u32) -> String {
+    format!(
+        "/deleteAfterDays/{}/{}",
+        client::encoded_entry_uri(bucket, key),
+        days
+    )
+}
+
+
+/// Returns if the operation can be safely retried, i.e. repeating it after
+/// it succeeded succeeds again with the same effect. Deleting and moving
+/// fail with 612 when repeated, as does copying without overwriting with 614,
+/// so a retry would report an operation that succeeded as failed.
+pub(super) fn is_idempotent_op(op: &str) -> bool {
+    match op.trim_left_matches('/').split('/').next() {
+        Some("stat") | Some("chgm") | Some("chtype") | Some("chstatus") | Some("deleteAfterDays") => true,
+        Some("copy") => op.ends_with("/force/true"),
+        _ => false,
+    }
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    fn req_rs_op(&self, op: String) -> Result<request::QiniuRequest> {
+        let url = self.hosts().rs().join(&op[1..])?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Rs))
+                .with_idempotent(is_idempotent_op(&op)),
+        )
+    }
+}
+
+
+#[cfg(feature = "async-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Delete the object.
+    pub fn delete<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_rs_op(delete_op(&bucket, &key));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Move (rename) the object, overwriting any existing destination if
+    /// `force` is set.
+    pub fn move_<'b: 'a>(
+        &'a self,
+        src_bucket: Cow<'b, str>,
+        src_key: Cow<'b, str>,
+        dest_bucket: Cow<'b, str>,
+        dest_key: Cow<'b, str>,
+        force: bool,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_rs_op(move_op(&src_bucket, &src_key, &dest_bucket, &dest_key, force));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Copy the object, overwriting any existing destination if `force` is
+    /// set.
+    pub fn copy<'b: 'a>(
+        &'a self,
+        src_bucket: Cow<'b, str>,
+        src_key: Cow<'b, str>,
+        dest_bucket: Cow<'b, str>,
+        dest_key: Cow<'b, str>,
+        force: bool,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_rs_op(copy_op(&src_bucket, &src_key, &dest_bucket, &dest_key, force));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Change the MIME type of the object.
+    pub fn change_mime<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+        mime_type: &str,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_rs_op(change_mime_op(&bucket, &key, mime_type));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Change the storage kind of the object.
+    pub fn change_type<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+        kind: StorageKind,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_rs_op(change_type_op(&bucket, &key, kind));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Enable or disable the object.
+    pub fn change_status<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+        status: ObjectStatus,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_rs_op(change_status_op(&bucket, &key, status));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Schedule deletion of the object after the given number of days, or
+    /// cancel any scheduled deletion if `days` is 0.
+    pub fn delete_after_days<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+        days: u32,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_rs_op(delete_after_days_op(&bucket, &key, days));
+        client::execute_empty(self.provider, req)
+    }
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Delete the object.
+    pub fn delete<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>) -> Result<()> {
+        let req = self.req_rs_op(delete_op(&bucket, &key));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Move (rename) the object, overwriting any existing destination if
+    /// `force` is set.
+    pub fn move_<'b: 'a>(
+        &'a self,
+        src_bucket: Cow<'b, str>,
+        src_key: Cow<'b, str>,
+        dest_bucket: Cow<'b, str>,
+        dest_key: Cow<'b, str>,
+        force: bool,
+    ) -> Result<()> {
+        let req = self.req_rs_op(move_op(&src_bucket, &src_key, &dest_bucket, &dest_key, force));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Copy the object, overwriting any existing destination if `force` is
+    /// set.
+    pub fn copy<'b: 'a>(
+        &'a self,
+        src_bucket: Cow<'b, str>,
+        src_key: Cow<'b, str>,
+        dest_bucket: Cow<'b, str>,
+        dest_key: Cow<'b, str>,
+        force: bool,
+    ) -> Result<()> {
+        let req = self.req_rs_op(copy_op(&src_bucket, &src_key, &dest_bucket, &dest_key, force));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Change the MIME type of the object.
+    pub fn change_mime<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>, mime_type: &str) -> Result<()> {
+        let req = self.req_rs_op(change_mime_op(&bucket, &key, mime_type));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Change the storage kind of the object.
+    pub fn change_type<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>, kind: StorageKind) -> Result<()> {
+        let req = self.req_rs_op(change_type_op(&bucket, &key, kind));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Enable or disable the object.
+    pub fn change_status<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+        status: ObjectStatus,
+    ) -> Result<()> {
+        let req = self.req_rs_op(change_status_op(&bucket, &key, status));
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Schedule deletion of the object after the given number of days, or
+    /// cancel any scheduled deletion if `days` is 0.
+    pub fn delete_after_days<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>, days: u32) -> Result<()> {
+        let req = self.req_rs_op(delete_after_days_op(&bucket, &key, days));
+        client::execute_empty(self.provider, req)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+
+    #[test]
+    fn test_stat_response() {
+        let resp: StatResponse = serde_json::from_str(concat!(
+            r#"{"fsize":5,"hash":"h","mimeType":"text/plain","putTime":15398208000000000,"type":2,"#,
+            r#""md5":"5d41402abc4b2a76b9719d911017c592","endUser":"u","restoreStatus":1,"#,
+            r#""expiration":1539820800,"status":1}"#
+        )).unwrap();
+        assert_eq!(resp.type_, StorageKind::Archive);
+        assert_eq!(resp.md5, Some("5d41402abc4b2a76b9719d911017c592".to_owned()));
+        assert_eq!(resp.end_user, Some("u".to_owned()));
+        assert_eq!(resp.restore_status, Some(1));
+        assert_eq!(resp.expiration, Some(1539820800));
+        assert_eq!(resp.status, Some(1));
+
+        let resp: StatResponse = serde_json::from_str(
+            r#"{"fsize":5,"hash":"h","mimeType":"text/plain","putTime":15398208000000000,"type":0}"#,
+        ).unwrap();
+        assert_eq!(resp.type_, StorageKind::Conventional);
+        assert_eq!(resp.md5, None);
+        assert_eq!(resp.end_user, None);
+        assert_eq!(resp.restore_status, None);
+        assert_eq!(resp.expiration, None);
+        assert_eq!(resp.status, None);
+    }
+
+    #[test]
+    fn test_move_op() {
+        // same resources as the signing test in `sign`
+        assert_eq!(
+            move_op("newdocs", "find_man.txt", "newdocs", "find.man.txt", false),
+            "/move/bmV3ZG9jczpmaW5kX21hbi50eHQ=/bmV3ZG9jczpmaW5kLm1hbi50eHQ=/force/false"
+        );
+    }
+
+    #[test]
+    fn test_object_ops() {
+        let entry = "bmV3ZG9jczpmaW5kX21hbi50eHQ=";
+        assert_eq!(delete_op("newdocs", "find_man.txt"), format!("/delete/{}", entry));
+        assert_eq!(
+            copy_op("newdocs", "find_man.txt", "newdocs", "find.man.txt", true),
+            format!("/copy/{}/bmV3ZG9jczpmaW5kLm1hbi50eHQ=/force/true", entry)
+        );
+        assert_eq!(
+            change_mime_op("newdocs", "find_man.txt", "text/plain"),
+            format!("/chgm/{}/mime/dGV4dC9wbGFpbg==", entry)
+        );
+        assert_eq!(
+            change_type_op("newdocs", "find_man.txt", StorageKind::LowFrequency),
+            format!("/chtype/{}/type/1", entry)
+        );
+        assert_eq!(
+            change_status_op("newdocs", "find_man.txt", ObjectStatus::Disabled),
+            format!("/chstatus/{}/status/1", entry)
+        );
+        assert_eq!(
+            change_status_op("newdocs", "find_man.txt", ObjectStatus::Enabled),
+            format!("/chstatus/{}/status/0", entry)
+        );
+        assert_eq!(
+            delete_after_days_op("newdocs", "find_man.txt", 7),
+            format!("/deleteAfterDays/{}/7", entry)
+        );
+    }
+
+    #[test]
+    fn test_is_idempotent_op() {
+        assert!(is_idempotent_op(&stat_op("b", "k")));
+        assert!(is_idempotent_op(&change_mime_op("b", "k", "text/plain")));
+        assert!(is_idempotent_op(&change_type_op("b", "k", StorageKind::Archive)));
+        assert!(is_idempotent_op(&change_status_op("b", "k", ObjectStatus::Disabled)));
+        assert!(is_idempotent_op(&delete_after_days_op("b", "k", 7)));
+        assert!(is_idempotent_op(&copy_op("b", "k", "b", "l", true)));
+        assert!(!is_idempotent_op(&copy_op("b", "k", "b", "l", false)));
+        assert!(!is_idempotent_op(&move_op("b", "k", "b", "l", true)));
+        assert!(!is_idempotent_op(&move_op("b", "k", "b", "l", false)));
+        assert!(!is_idempotent_op(&delete_op("b", "k")));
+        assert!(!is_idempotent_op(""));
+        assert!(!is_idempotent_op("/"));
+    }
+}
@@ -0,0 +1,205 @@
+//! [Mirror back-to-origin][mirror] configuration and prefetching.
+//!
+//! Retrying of mirror requests is not exposed by any documented uc or api
+//! endpoint, so only the origins can be configured.
+//!
+//! [mirror]: https://developer.qiniu.com/kodo/manual/1370/mirror
+
+use std::borrow::Cow;
+
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::bucket::RawBucketInfo;
+use super::client::{self, QiniuStorageClient};
+
+
+/// Mirror source of a bucket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorSource {
+    /// URLs of the origin sites, e.g. `https://origin.example.com`, tried in
+    /// order.
+    pub urls: Vec<String>,
+    /// `Host` header sent to the origins, if different from that of the URLs.
+    pub host: Option<String>,
+}
+
+
+impl MirrorSource {
+    /// Creates a mirror source of the single origin site.
+    pub fn new<S: Into<String>>(url: S) -> MirrorSource {
+        MirrorSource {
+            urls: vec![url.into()],
+            host: None,
+        }
+    }
+}
+
+
+fn non_empty(x: Option<String>) -> Option<String> {
+    x.and_then(|x| if x.is_empty() { None } else { Some(x) })
+}
+
+
+/// Origins are stored joined with `;` in the single `source` field.
+fn mirror_source_of(info: RawBucketInfo) -> Option<MirrorSource> {
+    non_empty(info.source).map(|source| {
+        MirrorSource {
+            urls: source.split(';').filter(|x| !x.is_empty()).map(String::from).collect(),
+            host: non_empty(info.host),
+        }
+    })
+}
+
+
+fn mirror_source_path(bucket: &str, source: Option<&MirrorSource>) -> Result<String> {
+    let bucket = client::bucket_segment(bucket)?;
+    let source = match source {
+        Some(source) => source,
+        None => return Ok(format!("unimage/{}", bucket)),
+    };
+    if source.urls.is_empty() || source.urls.iter().any(|x| x.is_empty() || x.contains(';')) {
+        bail!("invalid mirror source URLs {:?}", source.urls);
+    }
+
+    let mut tmp = format!(
+        "image/{}/from/{}",
+        bucket,
+        client::urlsafe_base64(source.urls.join(";"))
+    );
+    if let Some(ref host) = source.host {
+        tmp.push_str("/host/");
+        tmp.push_str(&client::urlsafe_base64(host));
+    }
+    Ok(tmp)
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    fn req_prefetch<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>) -> Result<request::QiniuRequest> {
+        let path = format!("prefetch/{}", client::encoded_entry_uri(&bucket, &key));
+        let url = self.hosts().io().join(&path)?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Io)),
+        )
+    }
+
+    fn req_set_mirror_source(&self, bucket: &str, source: Option<&MirrorSource>) -> Result<request::QiniuRequest> {
+        let url = self.hosts().uc().join(&mirror_source_path(bucket, source)?)?;
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, url, None)?
+                .with_content_type("application/x-www-form-urlencoded")
+                .with_endpoints(self.hosts().endpoints(provider::Service::Uc))
+                .with_idempotent(true),
+        )
+    }
+}
+
+
+#[cfg(feature = "async-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Refresh the object from the mirror source of the bucket.
+    pub fn prefetch<'b: 'a>(
+        &'a self,
+        bucket: Cow<'b, str>,
+        key: Cow<'b, str>,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_prefetch(bucket, key);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Returns the mirror source of the bucket, if any.
+    pub fn mirror_source(&self, bucket: &str) -> impl Future<Item = Option<MirrorSource>, Error = Error> {
+        let req = self.req_bucket_info(bucket);
+        client::execute_json(self.provider, req).map(mirror_source_of)
+    }
+
+    /// Set the mirror source of the bucket, or remove it if `None`.
+    pub fn set_mirror_source(
+        &self,
+        bucket: &str,
+        source: Option<&MirrorSource>,
+    ) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_set_mirror_source(bucket, source);
+        client::execute_empty(self.provider, req)
+    }
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> QiniuStorageClient<'a> {
+    /// Refresh the object from the mirror source of the bucket.
+    pub fn prefetch<'b: 'a>(&'a self, bucket: Cow<'b, str>, key: Cow<'b, str>) -> Result<()> {
+        let req = self.req_prefetch(bucket, key);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// Returns the mirror source of the bucket, if any.
+    pub fn mirror_source(&self, bucket: &str) -> Result<Option<MirrorSource>> {
+        let req = self.req_bucket_info(bucket);
+        Ok(mirror_source_of(client::execute_json(self.provider, req)?))
+    }
+
+    /// Set the mirror source of the bucket, or remove it if `None`.
+    pub fn set_mirror_source(&self, bucket: &str, source: Option<&MirrorSource>) -> Result<()> {
+        let req = self.req_set_mirror_source(bucket, source);
+        client::execute_empty(self.provider, req)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+
+    use super::*;
+
+
+    #[test]
+    fn test_mirror_source_of() {
+        let info = serde_json::from_str(r#"{"source":"https://origin.example.com","host":"","private":0}"#).unwrap();
+        assert_eq!(
+            mirror_source_of(info),
+            Some(MirrorSource::new("https://origin.example.com"))
+        );
+
+        let info = serde_json::from_str(r#"{"source":"http://a.com;http://b.com","host":"c.com"}"#).unwrap();
+        assert_eq!(
+            mirror_source_of(info),
+            Some(MirrorSource {
+                urls: vec!["http://a.com".to_owned(), "http://b.com".to_owned()],
+                host: Some("c.com".to_owned()),
+            })
+        );
+
+        let info = serde_json::from_str(r#"{"source":"","private":1}"#).unwrap();
+        assert_eq!(mirror_source_of(info), None);
+    }
+
+    #[test]
+    fn test_mirror_source_path() {
+        let source = MirrorSource {
+            urls: vec!["http://a.com".to_owned(), "http://b.com".to_owned()],
+            host: Some("c.com".to_owned()),
+        };
+        assert_eq!(
+            mirror_source_path("b", Some(&source)).unwrap(),
+            "image/b/from/aHR0cDovL2EuY29tO2h0dHA6Ly9iLmNvbQ==/host/Yy5jb20="
+        );
+        assert_eq!(mirror_source_path("b", None).unwrap(), "unimage/b");
+
+        assert!(mirror_source_path("b", Some(&MirrorSource { urls: vec![], host: None })).is_err());
+        assert!(mirror_source_path("b", Some(&MirrorSource::new("http://a.com;"))).is_err());
+
+        assert_eq!(mirror_source_path("a/b?c", None).unwrap(), "unimage/a%2Fb%3Fc");
+        assert!(mirror_source_path("..", Some(&source)).is_err());
+    }
+}
@@ -1,6 +1,28 @@
+mod batch;
+mod bucket;
+mod callback;
 mod client;
+mod download;
+mod fetch;
+mod lifecycle;
+mod manage;
+mod mirror;
+mod multipart_upload;
+mod resumable;
 mod types;
 mod tokens;
+mod upload;
 
+pub use self::batch::*;
+pub use self::bucket::*;
 pub use self::client::*;
+pub use self::download::*;
+pub use self::fetch::*;
+pub use self::lifecycle::*;
+pub use self::manage::*;
+pub use self::mirror::*;
+pub use self::multipart_upload::*;
+pub use self::resumable::*;
 pub use self::types::*;
+pub use self::tokens::UploadToken;
+pub use self::upload::*;
@@ -0,0 +1,572 @@
+//! [Multipart uploads][multipart-upload] with the v2 `initParts`/`uploadPart`/
+//! `completeParts` protocol.
+//!
+//! [multipart-upload]: https://developer.qiniu.com/kodo/api/6364/multipartupload-interface
+
+use std::collections::HashMap;
+
+use bytes;
+use serde;
+use serde_json;
+
+#[cfg(feature = "async-api")]
+use futures::{future, stream};
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+use super::upload::UploadExtra;
+
+
+/// Minimum part size allowed by the protocol, except for the last part.
+pub const MIN_PART_SIZE: u64 = 1 << 20;
+/// Maximum part size allowed by the protocol.
+pub const MAX_PART_SIZE: u64 = 1 << 30;
+/// Default part size.
+pub const DEFAULT_PART_SIZE: u64 = 4 << 20;
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InitPartsResponse {
+    pub upload_id: String,
+    /// Unix timestamp after which the upload ID is no longer valid.
+    pub expire_at: u64,
+}
+
+
+/// A successfully uploaded part.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadedPart {
+    pub part_number: usize,
+    pub etag: String,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListPartsResponse {
+    pub upload_id: String,
+    pub expire_at: u64,
+    /// Marker for fetching the next page of parts, 0 if there are no more.
+    pub part_number_marker: usize,
+    pub parts: Vec<ListedPart>,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListedPart {
+    pub part_number: usize,
+    pub etag: String,
+    pub size: u64,
+    pub put_time: u64,
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+struct UploadPartResponse {
+    etag: String,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CompletePartsRequest<'a> {
+    parts: &'a [UploadedPart],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fname: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mime_type: Option<&'a str>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    custom_vars: HashMap<String, &'a str>,
+}
+
+
+/// Path of the upload resources of the object, with `suffix` appended.
+fn upload_path(bucket: &str, key: Option<&str>, suffix: &str) -> Result<String> {
+    let object = key.map_or_else(|| "~".to_owned(), |k| client::urlsafe_base64(k));
+    Ok(format!(
+        "buckets/{}/objects/{}/uploads{}",
+        client::bucket_segment(bucket)?,
+        object,
+        suffix
+    ))
+}
+
+
+/// Body of the `completeParts` request, listing the parts in order.
+fn complete_parts_body(mut parts: Vec<UploadedPart>, extra: &UploadExtra) -> Result<Vec<u8>> {
+    parts.sort_by_key(|p| p.part_number);
+
+    let body = CompletePartsRequest {
+        parts: &parts,
+        fname: extra.file_name.as_ref().map(|x| x.as_str()),
+        mime_type: extra.mime_type.as_ref().map(|x| x.as_str()),
+        custom_vars: extra.custom_var_pairs().collect(),
+    };
+
+    Ok(serde_json::to_vec(&body)?)
+}
+
+
+/// Split the data into numbered parts of `part_size` bytes, except for the
+/// last one.
+fn split_parts(data: bytes::Bytes, part_size: u64) -> Vec<(usize, bytes::Bytes)> {
+    let part_size = part_size as usize;
+    let mut parts = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let end = ::std::cmp::min(offset + part_size, data.len());
+        parts.push((parts.len() + 1, data.slice(offset, end)));
+        offset = end;
+    }
+
+    parts
+}
+
+
+/// Parts of the data to upload, failing for empty data which the protocol
+/// can't complete without parts.
+fn parts_of(data: bytes::Bytes, part_size: u64) -> Result<Vec<(usize, bytes::Bytes)>> {
+    if data.is_empty() {
+        bail!("multipart uploads need at least one byte of data, use form uploads instead");
+    }
+
+    Ok(split_parts(data, part_size))
+}
+
+
+/// Uploader of files in parts of arbitrary size with the v2 protocol.
+///
+/// Obtain one with [QiniuStorageClient::multipart_uploader].
+///
+/// [QiniuStorageClient::multipart_uploader]: ./struct.QiniuStorageClient.html#method.multipart_uploader
+#[derive(Clone)]
+pub struct MultipartUploader<'a> {
+    provider: &'a provider::QiniuClient,
+    up: Vec<::url::Url>,
+    token: String,
+    bucket: String,
+    part_size: u64,
+    concurrency: usize,
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    /// Create a v2 multipart uploader for the bucket, using the given upload
+    /// token.
+    pub fn multipart_uploader(&self, token: String, bucket: String) -> MultipartUploader<'a> {
+        MultipartUploader {
+            provider: self.provider,
+            up: self.hosts().endpoints(provider::Service::Up).to_vec(),
+            token: token,
+            bucket: bucket,
+            part_size: DEFAULT_PART_SIZE,
+            concurrency: 1,
+        }
+    }
+}
+
+
+impl<'a> MultipartUploader<'a> {
+    /// Set the size of parts. The value is clamped into the range allowed by
+    /// the protocol.
+    pub fn part_size(mut self, part_size: u64) -> Self {
+        self.part_size = ::std::cmp::max(MIN_PART_SIZE, ::std::cmp::min(part_size, MAX_PART_SIZE));
+        self
+    }
+
+    /// Set the number of parts uploaded concurrently by [upload]. Only
+    /// meaningful for the async flavor, the sync flavor always uploads parts
+    /// one by one.
+    ///
+    /// [upload]: #method.upload
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = ::std::cmp::max(1, concurrency);
+        self
+    }
+
+    fn upload_url(&self, key: Option<&str>, suffix: &str) -> Result<::url::Url> {
+        Ok(self.up[0].join(&upload_path(&self.bucket, key, suffix)?)?)
+    }
+
+    fn req(
+        &self,
+        method: reqwest::Method,
+        url: Result<::url::Url>,
+        body: Option<bytes::Bytes>,
+        content_type: &str,
+    ) -> Result<request::QiniuRequest> {
+        Ok(
+            request::QiniuRequest::new(method, url?, body)?
+                .with_content_type(content_type)
+                .with_auth(request::Authorization::UpToken(self.token.clone()))
+                .with_endpoints(&self.up),
+        )
+    }
+
+    fn req_init_parts(&self, key: Option<&str>) -> Result<request::QiniuRequest> {
+        let url = self.upload_url(key, "");
+        self.req(reqwest::Method::Post, url, None, "application/json")
+    }
+
+    fn req_upload_part(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        part_number: usize,
+        data: bytes::Bytes,
+    ) -> Result<request::QiniuRequest> {
+        let url = self.upload_url(key, &format!("/{}/{}", upload_id, part_number));
+        self.req(
+            reqwest::Method::Put,
+            url,
+            Some(data),
+            "application/octet-stream",
+        )
+    }
+
+    fn req_complete_parts(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        parts: Vec<UploadedPart>,
+        extra: &UploadExtra,
+    ) -> Result<request::QiniuRequest> {
+        let body = complete_parts_body(parts, extra)?;
+        let url = self.upload_url(key, &format!("/{}", upload_id));
+        self.req(
+            reqwest::Method::Post,
+            url,
+            Some(body.into()),
+            "application/json",
+        )
+    }
+
+    fn req_abort(&self, key: Option<&str>, upload_id: &str) -> Result<request::QiniuRequest> {
+        let url = self.upload_url(key, &format!("/{}", upload_id));
+        self.req(reqwest::Method::Delete, url, None, "application/json")
+    }
+
+    fn req_list_parts(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        max_parts: Option<usize>,
+        part_number_marker: Option<usize>,
+    ) -> Result<request::QiniuRequest> {
+        let url = self.upload_url(key, &format!("/{}", upload_id)).map(
+            |mut url| {
+                {
+                    let mut qs = url.query_pairs_mut();
+                    if let Some(max_parts) = max_parts {
+                        qs.append_pair("max-parts", &format!("{}", max_parts));
+                    }
+                    if let Some(marker) = part_number_marker {
+                        qs.append_pair("part-number-marker", &format!("{}", marker));
+                    }
+                }
+                url
+            },
+        );
+        self.req(reqwest::Method::Get, url, None, "application/json")
+    }
+}
+
+
+#[cfg(feature = "async-api")]
+impl<'a> MultipartUploader<'a> {
+    /// Initiate a multipart upload.
+    pub fn init_parts(&self, key: Option<&str>) -> impl Future<Item = InitPartsResponse, Error = Error> {
+        let req = self.req_init_parts(key);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Upload one part. Part numbers start from 1.
+    pub fn upload_part(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        part_number: usize,
+        data: bytes::Bytes,
+    ) -> impl Future<Item = UploadedPart, Error = Error> {
+        let req = self.req_upload_part(key, upload_id, part_number, data);
+        client::execute_json(self.provider, req).map(move |resp: UploadPartResponse| {
+            UploadedPart {
+                part_number: part_number,
+                etag: resp.etag,
+            }
+        })
+    }
+
+    /// Complete a multipart upload, returning the decoded `returnBody` of the
+    /// put policy.
+    pub fn complete_parts<T>(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        parts: Vec<UploadedPart>,
+        extra: &UploadExtra,
+    ) -> impl Future<Item = T, Error = Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let req = self.req_complete_parts(key, upload_id, parts, extra);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Abort a multipart upload, discarding all uploaded parts.
+    pub fn abort(&self, key: Option<&str>, upload_id: &str) -> impl Future<Item = (), Error = Error> {
+        let req = self.req_abort(key, upload_id);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// List the uploaded parts of a multipart upload.
+    pub fn list_parts(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        max_parts: Option<usize>,
+        part_number_marker: Option<usize>,
+    ) -> impl Future<Item = ListPartsResponse, Error = Error> {
+        let req = self.req_list_parts(key, upload_id, max_parts, part_number_marker);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Upload the data in parts, with up to `concurrency` parts in flight at
+    /// the same time.
+    ///
+    /// The data must not be empty. The upload is aborted if any part fails.
+    pub fn upload<T>(
+        &self,
+        key: Option<&str>,
+        data: bytes::Bytes,
+        extra: &UploadExtra,
+    ) -> impl Future<Item = T, Error = Error> + 'a
+    where
+        T: serde::de::DeserializeOwned + 'a,
+    {
+        let this = self.clone();
+        let key = key.map(|x| x.to_owned());
+        let extra = extra.clone();
+
+        let x = future::result(parts_of(data, self.part_size));
+        let x = {
+            let this = this.clone();
+            let key = key.clone();
+            x.and_then(move |parts| {
+                this.init_parts(key.as_ref().map(|x| x.as_str())).map(
+                    move |init| (parts, init),
+                )
+            })
+        };
+
+        x.and_then(move |(parts, init)| {
+            let upload_id = init.upload_id;
+            let concurrency = this.concurrency;
+
+            let x = {
+                let this = this.clone();
+                let key = key.clone();
+                let upload_id = upload_id.clone();
+                stream::iter_ok(parts)
+                    .map(move |(part_number, data)| {
+                        this.upload_part(key.as_ref().map(|x| x.as_str()), &upload_id, part_number, data)
+                    })
+                    .buffer_unordered(concurrency)
+                    .collect()
+            };
+
+            let x = {
+                let this = this.clone();
+                let key = key.clone();
+                let upload_id = upload_id.clone();
+                x.and_then(move |parts| {
+                    this.complete_parts(key.as_ref().map(|x| x.as_str()), &upload_id, parts, &extra)
+                })
+            };
+
+            // don't leave the parts dangling, keeping the original error
+            x.or_else(move |e| {
+                this.abort(key.as_ref().map(|x| x.as_str()), &upload_id).then(
+                    move |_| Err(e),
+                )
+            })
+        })
+    }
+}
+
+
+#[cfg(feature = "sync-api")]
+impl<'a> MultipartUploader<'a> {
+    /// Initiate a multipart upload.
+    pub fn init_parts(&self, key: Option<&str>) -> Result<InitPartsResponse> {
+        let req = self.req_init_parts(key);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Upload one part. Part numbers start from 1.
+    pub fn upload_part(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        part_number: usize,
+        data: bytes::Bytes,
+    ) -> Result<UploadedPart> {
+        let req = self.req_upload_part(key, upload_id, part_number, data);
+        let resp: UploadPartResponse = client::execute_json(self.provider, req)?;
+
+        Ok(UploadedPart {
+            part_number: part_number,
+            etag: resp.etag,
+        })
+    }
+
+    /// Complete a multipart upload, returning the decoded `returnBody` of the
+    /// put policy.
+    pub fn complete_parts<T>(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        parts: Vec<UploadedPart>,
+        extra: &UploadExtra,
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let req = self.req_complete_parts(key, upload_id, parts, extra);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Abort a multipart upload, discarding all uploaded parts.
+    pub fn abort(&self, key: Option<&str>, upload_id: &str) -> Result<()> {
+        let req = self.req_abort(key, upload_id);
+        client::execute_empty(self.provider, req)
+    }
+
+    /// List the uploaded parts of a multipart upload.
+    pub fn list_parts(
+        &self,
+        key: Option<&str>,
+        upload_id: &str,
+        max_parts: Option<usize>,
+        part_number_marker: Option<usize>,
+    ) -> Result<ListPartsResponse> {
+        let req = self.req_list_parts(key, upload_id, max_parts, part_number_marker);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Upload the data in parts, one part at a time.
+    ///
+    /// The data must not be empty. The upload is aborted if any part fails.
+    pub fn upload<T>(&self, key: Option<&str>, data: bytes::Bytes, extra: &UploadExtra) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let parts = parts_of(data, self.part_size)?;
+        let init = self.init_parts(key)?;
+
+        let x = (|| {
+            let mut uploaded = Vec::with_capacity(parts.len());
+            for (part_number, data) in parts {
+                uploaded.push(self.upload_part(
+                    key,
+                    &init.upload_id,
+                    part_number,
+                    data,
+                )?);
+            }
+
+            self.complete_parts(key, &init.upload_id, uploaded, extra)
+        })();
+
+        if x.is_err() {
+            // don't leave the parts dangling, keeping the original error
+            let _ = self.abort(key, &init.upload_id);
+        }
+
+        x
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_split_parts() {
+        let data = bytes::Bytes::from(&b"abcdefg"[..]);
+
+        assert_eq!(
+            split_parts(data.clone(), 3),
+            vec![(1, b"abc"[..].into()), (2, b"def"[..].into()), (3, b"g"[..].into())]
+        );
+        assert_eq!(split_parts(data.clone(), 7), vec![(1, data.clone())]);
+        assert_eq!(split_parts(data.clone(), 8), vec![(1, data.clone())]);
+        assert_eq!(split_parts(data.slice(0, 6), 3).len(), 2);
+        assert!(split_parts(bytes::Bytes::new(), 3).is_empty());
+
+        assert!(parts_of(bytes::Bytes::new(), 3).is_err());
+        assert_eq!(parts_of(data.clone(), 3).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_complete_parts_body() {
+        let parts = vec![
+            UploadedPart {
+                part_number: 2,
+                etag: "etag2".to_owned(),
+            },
+            UploadedPart {
+                part_number: 1,
+                etag: "etag1".to_owned(),
+            },
+        ];
+        let extra = UploadExtra {
+            mime_type: Some("video/mp4".to_owned()),
+            custom_vars: vec![("user".to_owned(), "42".to_owned())],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            String::from_utf8(complete_parts_body(parts, &extra).unwrap()).unwrap(),
+            concat!(
+                r#"{"parts":[{"partNumber":1,"etag":"etag1"},{"partNumber":2,"etag":"etag2"}],"#,
+                r#""mimeType":"video/mp4","customVars":{"x:user":"42"}}"#
+            )
+        );
+        assert_eq!(
+            String::from_utf8(complete_parts_body(Vec::new(), &UploadExtra::default()).unwrap()).unwrap(),
+            r#"{"parts":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_upload_path() {
+        // initParts
+        assert_eq!(
+            upload_path("b", Some("a/b.mp4"), "").unwrap(),
+            "buckets/b/objects/YS9iLm1wNA==/uploads"
+        );
+        assert_eq!(upload_path("b", None, "").unwrap(), "buckets/b/objects/~/uploads");
+        // uploadPart
+        assert_eq!(
+            upload_path("b", Some("a/b.mp4"), "/id/3").unwrap(),
+            "buckets/b/objects/YS9iLm1wNA==/uploads/id/3"
+        );
+
+        assert_eq!(
+            upload_path("a/b?c", None, "").unwrap(),
+            "buckets/a%2Fb%3Fc/objects/~/uploads"
+        );
+        assert!(upload_path("..", None, "").is_err());
+    }
+}
@@ -0,0 +1,714 @@
+//! [Resumable uploads][resumable-upload] with the `mkblk`/`bput`/`mkfile`
+//! protocol.
+//!
+//! [resumable-upload]: https://developer.qiniu.com/kodo/api/1311/mkblk
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "async-api")]
+use std::thread;
+use std::time;
+
+use bytes;
+use crc;
+use ring;
+use serde;
+use serde_json;
+use url;
+
+#[cfg(feature = "async-api")]
+use futures::stream;
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+#[cfg(feature = "async-api")]
+use futures::sync::oneshot;
+
+use super::super::errors::*;
+use super::super::provider;
+use super::super::qetag;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+use super::upload::UploadExtra;
+
+
+/// Size of blocks in resumable uploads, fixed by the protocol to 4 MiB.
+pub const BLOCK_SIZE: u64 = 4 << 20;
+
+
+/// Context of an uploaded block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockContext {
+    pub ctx: String,
+    /// Unix timestamp after which the context is no longer valid.
+    pub expired_at: u64,
+}
+
+
+/// Progress of a resumable upload, i.e. contexts of the completed blocks
+/// starting from the first one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumeRecord {
+    pub file_size: u64,
+    /// Fingerprint of the data being uploaded, i.e. the etag of its first
+    /// block and the source version if set. Records saved without one are
+    /// never resumed from.
+    #[serde(default)]
+    pub fingerprint: String,
+    pub contexts: Vec<BlockContext>,
+}
+
+
+impl ResumeRecord {
+    fn new(file_size: u64, fingerprint: String) -> ResumeRecord {
+        ResumeRecord {
+            file_size: file_size,
+            fingerprint: fingerprint,
+            contexts: Vec::new(),
+        }
+    }
+
+    /// Returns if the record can be used to resume an upload of the given
+    /// size and fingerprint.
+    fn is_usable_for(&self, file_size: u64, fingerprint: &str) -> bool {
+        let now = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+
+        self.file_size == file_size && !self.fingerprint.is_empty() && self.fingerprint == fingerprint &&
+            (self.contexts.len() as u64) <= num_blocks(file_size) &&
+            self.contexts.iter().all(|c| c.expired_at > now)
+    }
+}
+
+
+/// Fingerprint of the data being uploaded, i.e. the etag of its first block,
+/// followed by the version of the source if given.
+///
+/// The version should change whenever the source does, e.g. the modification
+/// time of files, as changes past the first block are not detected otherwise.
+fn fingerprint(first_block: &[u8], version: Option<&str>) -> String {
+    let mut tmp = qetag::etag(first_block);
+    if let Some(version) = version {
+        tmp.push('/');
+        tmp.push_str(version);
+    }
+
+    tmp
+}
+
+
+/// Persistent storage of resumable upload progress.
+pub trait ResumeRecordStore {
+    /// Load the record with the given ID, if any.
+    fn load(&self, id: &str) -> Result<Option<ResumeRecord>>;
+    /// Save the record under the given ID, replacing any previous one.
+    fn save(&self, id: &str, record: &ResumeRecord) -> Result<()>;
+    /// Remove the record with the given ID. Removing a non-existent record
+    /// is not an error.
+    fn remove(&self, id: &str) -> Result<()>;
+}
+
+
+/// In-memory record store, useful for retrying uploads within a process.
+#[derive(Default)]
+pub struct MemoryRecordStore {
+    records: Mutex<HashMap<String, ResumeRecord>>,
+}
+
+
+impl MemoryRecordStore {
+    pub fn new() -> MemoryRecordStore {
+        MemoryRecordStore::default()
+    }
+}
+
+
+impl ResumeRecordStore for MemoryRecordStore {
+    fn load(&self, id: &str) -> Result<Option<ResumeRecord>> {
+        let records = self.records.lock().map_err(
+            |_| Error::from("record store poisoned"),
+        )?;
+        Ok(records.get(id).cloned())
+    }
+
+    fn save(&self, id: &str, record: &ResumeRecord) -> Result<()> {
+        let mut records = self.records.lock().map_err(
+            |_| Error::from("record store poisoned"),
+        )?;
+        records.insert(id.to_owned(), record.clone());
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<()> {
+        let mut records = self.records.lock().map_err(
+            |_| Error::from("record store poisoned"),
+        )?;
+        records.remove(id);
+        Ok(())
+    }
+}
+
+
+/// Record store keeping one JSON file per upload inside a directory.
+pub struct FileRecordStore {
+    dir: path::PathBuf,
+}
+
+
+impl FileRecordStore {
+    /// Use the given directory for storing records, creating it if necessary.
+    pub fn new<P: Into<path::PathBuf>>(dir: P) -> Result<FileRecordStore> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(FileRecordStore { dir: dir })
+    }
+
+    fn path_for(&self, id: &str) -> path::PathBuf {
+        // IDs are arbitrary strings, hash them into safe file names
+        let digest = ring::digest::digest(&ring::digest::SHA1, id.as_bytes());
+        let mut name = String::new();
+        for b in digest.as_ref() {
+            name.push_str(&format!("{:02x}", b));
+        }
+        name.push_str(".json");
+
+        self.dir.join(name)
+    }
+}
+
+
+impl ResumeRecordStore for FileRecordStore {
+    fn load(&self, id: &str) -> Result<Option<ResumeRecord>> {
+        match fs::File::open(self.path_for(id)) {
+            Ok(f) => Ok(Some(serde_json::from_reader(f)?)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, id: &str, record: &ResumeRecord) -> Result<()> {
+        let f = fs::File::create(self.path_for(id))?;
+        serde_json::to_writer(f, record)?;
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<()> {
+        match fs::remove_file(self.path_for(id)) {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Deserialize)]
+struct BlockPutResponse {
+    ctx: String,
+    crc32: u32,
+    offset: u64,
+    expired_at: u64,
+}
+
+
+fn num_blocks(file_size: u64) -> u64 {
+    (file_size + BLOCK_SIZE - 1) / BLOCK_SIZE
+}
+
+
+fn block_len(file_size: u64, idx: u64) -> u64 {
+    ::std::cmp::min(BLOCK_SIZE, file_size - idx * BLOCK_SIZE)
+}
+
+
+/// Read the block with the given index from the reader.
+fn read_block<R: Read + Seek>(reader: &mut R, file_size: u64, idx: u64) -> Result<bytes::Bytes> {
+    let mut block = vec![0u8; block_len(file_size, idx) as usize];
+    reader.seek(io::SeekFrom::Start(idx * BLOCK_SIZE))?;
+    reader.read_exact(&mut block)?;
+
+    Ok(block.into())
+}
+
+
+/// Read the block on a helper thread, handing the reader back with it, so
+/// that blocking reads don't stall the reactor.
+#[cfg(feature = "async-api")]
+fn read_block_off_reactor<R>(mut reader: R, file_size: u64, idx: u64) -> impl Future<Item = (bytes::Bytes, R), Error = Error>
+where
+    R: Read + Seek + Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    thread::spawn(move || {
+        let x = read_block(&mut reader, file_size, idx).map(|block| (block, reader));
+        let _ = tx.send(x);
+    });
+
+    rx.map_err(|_| Error::from("block reader thread panicked")).and_then(|x| x)
+}
+
+
+/// Split the block into chunks of at most `chunk_size` bytes.
+fn split_chunks(block: &bytes::Bytes, chunk_size: u64) -> Vec<bytes::Bytes> {
+    let block_size = block.len() as u64;
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    while offset < block_size {
+        let end = ::std::cmp::min(offset + chunk_size, block_size);
+        chunks.push(block.slice(offset as usize, end as usize));
+        offset = end;
+    }
+
+    chunks
+}
+
+
+fn check_crc32(chunk: &[u8], resp: &BlockPutResponse) -> Result<()> {
+    let expected = crc::crc32::checksum_ieee(chunk);
+    if resp.crc32 != expected {
+        bail!(
+            "crc32 mismatch on uploaded chunk: expected {}, got {}",
+            expected,
+            resp.crc32
+        );
+    }
+
+    Ok(())
+}
+
+
+fn req_mkblk(
+    up: &[url::Url],
+    token: &str,
+    block_size: u64,
+    chunk: bytes::Bytes,
+) -> Result<request::QiniuRequest> {
+    let url = up[0].join(
+        &format!("mkblk/{}", block_size),
+    )?;
+
+    // a repeated mkblk just creates another block context
+    Ok(
+        request::QiniuRequest::new(reqwest::Method::Post, url, Some(chunk))?
+            .with_content_type("application/octet-stream")
+            .with_auth(request::Authorization::UpToken(token.to_owned()))
+            .with_endpoints(up)
+            .with_idempotent(true),
+    )
+}
+
+
+fn req_bput(
+    up: &[url::Url],
+    token: &str,
+    ctx: &str,
+    offset: u64,
+    chunk: bytes::Bytes,
+) -> Result<request::QiniuRequest> {
+    let url = up[0].join(
+        &format!("bput/{}/{}", ctx, offset),
+    )?;
+
+    // the chunk is written at the offset of the context, so repeating it
+    // writes the same data again
+    Ok(
+        request::QiniuRequest::new(reqwest::Method::Post, url, Some(chunk))?
+            .with_content_type("application/octet-stream")
+            .with_auth(request::Authorization::UpToken(token.to_owned()))
+            .with_endpoints(up)
+            .with_idempotent(true),
+    )
+}
+
+
+fn req_mkfile(
+    up: &[url::Url],
+    token: &str,
+    key: Option<&str>,
+    extra: &UploadExtra,
+    record: &ResumeRecord,
+) -> Result<request::QiniuRequest> {
+    let mut path = format!("mkfile/{}", record.file_size);
+    if let Some(key) = key {
+        path.push_str("/key/");
+        path.push_str(&client::urlsafe_base64(key));
+    }
+    if let Some(ref file_name) = extra.file_name {
+        path.push_str("/fname/");
+        path.push_str(&client::urlsafe_base64(file_name));
+    }
+    if let Some(ref mime_type) = extra.mime_type {
+        path.push_str("/mimeType/");
+        path.push_str(&client::urlsafe_base64(mime_type));
+    }
+    for (k, v) in extra.custom_var_pairs() {
+        path.push('/');
+        path.push_str(&k);
+        path.push('/');
+        path.push_str(&client::urlsafe_base64(v));
+    }
+
+    let url = up[0].join(&path)?;
+    let body = record
+        .contexts
+        .iter()
+        .map(|c| c.ctx.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Ok(
+        request::QiniuRequest::new(reqwest::Method::Post, url, Some(body.into()))?
+            .with_content_type("text/plain")
+            .with_auth(request::Authorization::UpToken(token.to_owned()))
+            .with_endpoints(up),
+    )
+}
+
+
+/// Uploader of large files in blocks, able to resume interrupted uploads.
+///
+/// Obtain one with [QiniuStorageClient::resumable_uploader].
+///
+/// [QiniuStorageClient::resumable_uploader]: ./struct.QiniuStorageClient.html#method.resumable_uploader
+#[derive(Clone)]
+pub struct ResumableUploader<'a> {
+    provider: &'a provider::QiniuClient,
+    up: Vec<url::Url>,
+    token: String,
+    chunk_size: u64,
+    store: Option<(Arc<ResumeRecordStore + Send + Sync>, String)>,
+    version: Option<String>,
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    /// Create a resumable uploader using the given upload token.
+    pub fn resumable_uploader(&self, token: String) -> ResumableUploader<'a> {
+        ResumableUploader {
+            provider: self.provider,
+            up: self.hosts().endpoints(provider::Service::Up).to_vec(),
+            token: token,
+            chunk_size: BLOCK_SIZE,
+            store: None,
+            version: None,
+        }
+    }
+}
+
+
+impl<'a> ResumableUploader<'a> {
+    /// Set the size of chunks each block is uploaded in.
+    ///
+    /// Defaults to the block size, i.e. one request per block. The value is
+    /// clamped to at most the block size.
+    pub fn chunk_size(mut self, chunk_size: u64) -> Self {
+        self.chunk_size = ::std::cmp::max(1, ::std::cmp::min(chunk_size, BLOCK_SIZE));
+        self
+    }
+
+    /// Persist progress to the record store under the given ID, and resume
+    /// from any previously saved progress.
+    ///
+    /// The record is removed once the upload completes.
+    pub fn record_store(mut self, store: Arc<ResumeRecordStore + Send + Sync>, id: String) -> Self {
+        self.store = Some((store, id));
+        self
+    }
+
+    /// Set the version of the source, e.g. the modification time of the
+    /// file, so that saved progress is discarded once the source changes.
+    ///
+    /// Without a version, only changes of the size or the first block are
+    /// detected.
+    pub fn source_version(mut self, version: String) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// Load the saved progress if it is for the same data, i.e. with the same
+    /// size and fingerprint.
+    fn load_record(&self, file_size: u64, first_block: &[u8]) -> Result<ResumeRecord> {
+        let fingerprint = fingerprint(first_block, self.version.as_ref().map(|x| x.as_str()));
+        if let Some((ref store, ref id)) = self.store {
+            if let Some(record) = store.load(id)? {
+                if record.is_usable_for(file_size, &fingerprint) {
+                    return Ok(record);
+                }
+            }
+        }
+
+        Ok(ResumeRecord::new(file_size, fingerprint))
+    }
+}
+
+
+fn save_record(
+    store: &Option<(Arc<ResumeRecordStore + Send + Sync>, String)>,
+    record: &ResumeRecord,
+) -> Result<()> {
+    if let Some((ref store, ref id)) = *store {
+        store.save(id, record)?;
+    }
+
+    Ok(())
+}
+
+
+fn remove_record(store: &Option<(Arc<ResumeRecordStore + Send + Sync>, String)>) -> Result<()> {
+    if let Some((ref store, ref id)) = *store {
+        store.remove(id)?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(feature = "async-api")]
+fn upload_block<'a>(
+    provider: &'a provider::QiniuClient,
+    up: Vec<url::Url>,
+    token: String,
+    block: bytes::Bytes,
+    chunk_size: u64,
+) -> impl Future<Item = BlockContext, Error = Error> + 'a {
+    let block_size = block.len() as u64;
+    let mut chunks = split_chunks(&block, chunk_size);
+
+    let first = chunks.remove(0);
+    let req = req_mkblk(&up, &token, block_size, first.clone());
+    let x = client::execute_json(provider, req).and_then(move |resp: BlockPutResponse| {
+        check_crc32(&first, &resp).map(|_| resp)
+    });
+    let x = x.and_then(move |resp| {
+        stream::iter_ok(chunks).fold(resp, move |resp, chunk| {
+            let req = req_bput(&up, &token, &resp.ctx, resp.offset, chunk.clone());
+            client::execute_json(provider, req).and_then(move |resp: BlockPutResponse| {
+                check_crc32(&chunk, &resp).map(|_| resp)
+            })
+        })
+    });
+
+    x.map(|resp| {
+        BlockContext {
+            ctx: resp.ctx,
+            expired_at: resp.expired_at,
+        }
+    })
+}
+
+
+#[cfg(feature = "sync-api")]
+fn upload_block(
+    provider: &provider::QiniuClient,
+    up: &[url::Url],
+    token: &str,
+    block: bytes::Bytes,
+    chunk_size: u64,
+) -> Result<BlockContext> {
+    let block_size = block.len() as u64;
+    let mut chunks = split_chunks(&block, chunk_size).into_iter();
+
+    // blocks are never empty, so there is always a first chunk
+    let first = chunks.next().unwrap();
+    let req = req_mkblk(up, token, block_size, first.clone());
+    let mut resp: BlockPutResponse = client::execute_json(provider, req)?;
+    check_crc32(&first, &resp)?;
+
+    for chunk in chunks {
+        let req = req_bput(up, token, &resp.ctx, resp.offset, chunk.clone());
+        resp = client::execute_json(provider, req)?;
+        check_crc32(&chunk, &resp)?;
+    }
+
+    Ok(BlockContext {
+        ctx: resp.ctx,
+        expired_at: resp.expired_at,
+    })
+}
+
+
+impl<'a> ResumableUploader<'a> {
+    /// Upload `file_size` bytes from the reader, resuming from saved progress
+    /// if a record store is configured.
+    ///
+    /// Blocks are read from the reader as they are uploaded, on a helper
+    /// thread so as not to block the reactor. The response is the decoded
+    /// `returnBody` of the put policy, as in form uploads.
+    #[cfg(feature = "async-api")]
+    pub fn upload<T, R>(
+        &self,
+        key: Option<&str>,
+        reader: R,
+        file_size: u64,
+        extra: &UploadExtra,
+    ) -> impl Future<Item = T, Error = Error> + 'a
+    where
+        T: serde::de::DeserializeOwned + 'a,
+        R: Read + Seek + Send + 'static,
+    {
+        let provider = self.provider;
+        let up = self.up.clone();
+        let token = self.token.clone();
+        let chunk_size = self.chunk_size;
+        let store = self.store.clone();
+        let key = key.map(|x| x.to_owned());
+        let extra = extra.clone();
+
+        let this = self.clone();
+        let x = read_block_off_reactor(reader, file_size, 0).and_then(move |(first, reader)| {
+            this.load_record(file_size, &first).map(|record| (record, reader))
+        });
+        let x = {
+            let up = up.clone();
+            let token = token.clone();
+            let store = store.clone();
+            x.and_then(move |(record, reader)| {
+                let pending = (record.contexts.len() as u64)..num_blocks(file_size);
+
+                stream::iter_ok(pending).fold((record, reader), move |(mut record, reader), idx| {
+                    let store = store.clone();
+                    let (up, token) = (up.clone(), token.clone());
+                    read_block_off_reactor(reader, file_size, idx).and_then(move |(block, reader)| {
+                        upload_block(provider, up, token, block, chunk_size).and_then(
+                            move |ctx| -> Result<(ResumeRecord, R)> {
+                                record.contexts.push(ctx);
+                                save_record(&store, &record)?;
+                                Ok((record, reader))
+                            },
+                        )
+                    })
+                })
+            })
+        };
+        let x = x.and_then(move |(record, _)| {
+            let req = req_mkfile(&up, &token, key.as_ref().map(|x| x.as_str()), &extra, &record);
+            client::execute_json(provider, req)
+        });
+        let x = x.and_then(move |resp: T| -> Result<T> {
+            remove_record(&store)?;
+            Ok(resp)
+        });
+
+        x
+    }
+
+    /// Upload `file_size` bytes from the reader, resuming from saved progress
+    /// if a record store is configured.
+    ///
+    /// Blocks are read from the reader as they are uploaded. The response is
+    /// the decoded `returnBody` of the put policy, as in form uploads.
+    #[cfg(feature = "sync-api")]
+    pub fn upload<T, R>(
+        &self,
+        key: Option<&str>,
+        mut reader: R,
+        file_size: u64,
+        extra: &UploadExtra,
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        R: Read + Seek,
+    {
+        let first = read_block(&mut reader, file_size, 0)?;
+        let mut record = self.load_record(file_size, &first)?;
+
+        for idx in (record.contexts.len() as u64)..num_blocks(file_size) {
+            let block = read_block(&mut reader, file_size, idx)?;
+            let ctx = upload_block(self.provider, &self.up, &self.token, block, self.chunk_size)?;
+            record.contexts.push(ctx);
+            save_record(&self.store, &record)?;
+        }
+
+        let req = req_mkfile(&self.up, &self.token, key, extra, &record);
+        let resp = client::execute_json(self.provider, req)?;
+        remove_record(&self.store)?;
+
+        Ok(resp)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn test_block_layout() {
+        assert_eq!(num_blocks(0), 0);
+        assert_eq!(num_blocks(1), 1);
+        assert_eq!(num_blocks(BLOCK_SIZE), 1);
+        assert_eq!(num_blocks(BLOCK_SIZE + 1), 2);
+        assert_eq!(block_len(BLOCK_SIZE + 1, 0), BLOCK_SIZE);
+        assert_eq!(block_len(BLOCK_SIZE + 1, 1), 1);
+
+        let block = bytes::Bytes::from(&b"abcde"[..]);
+        assert_eq!(split_chunks(&block, 2), vec![&b"ab"[..], &b"cd"[..], &b"e"[..]]);
+        assert_eq!(split_chunks(&block, 5), vec![&b"abcde"[..]]);
+    }
+
+    #[test]
+    fn test_record_fingerprint() {
+        let mut record = ResumeRecord::new(4, fingerprint(b"data", Some("1539820800")));
+        assert!(record.is_usable_for(4, &fingerprint(b"data", Some("1539820800"))));
+        assert!(!record.is_usable_for(4, &fingerprint(b"date", Some("1539820800"))));
+        assert!(!record.is_usable_for(4, &fingerprint(b"data", Some("1539820801"))));
+        assert!(!record.is_usable_for(4, &fingerprint(b"data", None)));
+
+        // records saved by older versions have no fingerprint
+        record.fingerprint = String::new();
+        assert!(!record.is_usable_for(4, ""));
+
+        let record: ResumeRecord = serde_json::from_str(r#"{"file_size":4,"contexts":[]}"#).unwrap();
+        assert_eq!(record.fingerprint, "");
+    }
+
+    #[test]
+    fn test_read_block() {
+        let data = vec![7u8; BLOCK_SIZE as usize + 3];
+        let mut reader = io::Cursor::new(&data[..]);
+
+        assert_eq!(read_block(&mut reader, data.len() as u64, 1).unwrap(), &[7u8; 3][..]);
+        assert_eq!(read_block(&mut reader, data.len() as u64, 0).unwrap().len() as u64, BLOCK_SIZE);
+        assert_eq!(read_block(&mut reader, 0, 0).unwrap().len(), 0);
+        assert!(read_block(&mut reader, data.len() as u64 + 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_memory_record_store() {
+        let store = MemoryRecordStore::new();
+        let record = ResumeRecord {
+            file_size: 42,
+            fingerprint: fingerprint(b"data", None),
+            contexts: vec![
+                BlockContext {
+                    ctx: "ctx".to_owned(),
+                    expired_at: u64::max_value(),
+                },
+            ],
+        };
+
+        assert_eq!(store.load("a").unwrap(), None);
+        store.save("a", &record).unwrap();
+        assert_eq!(store.load("a").unwrap(), Some(record.clone()));
+        assert!(record.is_usable_for(42, &fingerprint(b"data", None)));
+        assert!(!record.is_usable_for(43, &fingerprint(b"data", None)));
+        store.remove("a").unwrap();
+        assert_eq!(store.load("a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_block_requests_idempotent() {
+        let up = vec![url::Url::parse("https://upload.qiniup.com").unwrap()];
+        let chunk = bytes::Bytes::from(&b"data"[..]);
+
+        assert!(req_mkblk(&up, "token", 4, chunk.clone()).unwrap().is_idempotent());
+        assert!(req_bput(&up, "token", "ctx", 4, chunk).unwrap().is_idempotent());
+    }
+}
@@ -3,9 +3,13 @@
 //! [upload-token]: https://developer.qiniu.com/kodo/manual/1208/upload-token
 //! [download-token]: https://developer.qiniu.com/kodo/manual/1202/download-token
 
+use std::time;
+
 use base64;
+use ring;
 use serde_json;
 
+use super::super::errors::*;
 use super::super::sign;
 use super::types::PutPolicy;
 
@@ -24,10 +28,109 @@ impl PutPolicy {
 }
 
 
+/// Upload token parsed into its parts.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UploadToken {
+    access_key: String,
+    signature: String,
+    encoded_policy: String,
+    policy: PutPolicy,
+}
+
+
+impl UploadToken {
+    /// Parse the upload token, without verifying its signature.
+    pub fn parse(token: &str) -> Result<UploadToken> {
+        let parts: Vec<&str> = token.splitn(3, ':').collect();
+        if parts.len() != 3 {
+            bail!("malformed upload token");
+        }
+
+        let json = base64::decode_config(parts[2], base64::URL_SAFE)?;
+        let policy = serde_json::from_slice(&json)?;
+
+        Ok(UploadToken {
+            access_key: parts[0].to_owned(),
+            signature: parts[1].to_owned(),
+            encoded_policy: parts[2].to_owned(),
+            policy: policy,
+        })
+    }
+
+    pub fn access_key(&self) -> &str {
+        &self.access_key
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    pub fn policy(&self) -> &PutPolicy {
+        &self.policy
+    }
+
+    pub fn into_policy(self) -> PutPolicy {
+        self.policy
+    }
+
+    /// Returns if the token is signed by the signer.
+    pub fn verify(&self, signer: &sign::QiniuSigner) -> bool {
+        let expected = signer.sign_blob(self.encoded_policy.as_bytes());
+
+        let mut actual = self.access_key.clone();
+        actual.push(':');
+        actual.push_str(&self.signature);
+
+        ring::constant_time::verify_slices_are_equal(expected.as_bytes(), actual.as_bytes()).is_ok()
+    }
+
+    /// Returns the number of seconds until the deadline of the policy,
+    /// relative to the given Unix timestamp. Negative if already expired.
+    pub fn expires_in_at(&self, now: u64) -> i64 {
+        self.policy.deadline() as i64 - now as i64
+    }
+
+    /// Returns the number of seconds until the deadline of the policy.
+    /// Negative if already expired.
+    pub fn expires_in(&self) -> i64 {
+        let now = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or(0);
+        self.expires_in_at(now)
+    }
+
+    /// Returns if the deadline of the policy has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires_in() < 0
+    }
+}
+
+
+/// Sign the download URL with the deadline, appending the `e` and `token`
+/// query parameters.
+///
+/// The URL is signed verbatim, so it must already be properly escaped.
+/// Existing query strings, including data processing commands like
+/// `?imageView2/1/w/100`, are preserved.
+pub(crate) fn sign_download_url(signer: &sign::QiniuSigner, url: &str, deadline: u32) -> String {
+    let mut tmp = url.to_owned();
+    tmp.push(if url.contains('?') { '&' } else { '?' });
+    tmp.push_str(&format!("e={}", deadline));
+
+    let token = signer.sign_blob(tmp.as_bytes());
+    tmp.push_str("&token=");
+    tmp.push_str(&token);
+
+    tmp
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::super::super::sign;
     use super::super::types;
+    use super::*;
 
     #[test]
     fn test_put_policy() {
@@ -36,7 +139,8 @@ mod tests {
         let b = r#"{"name":$(fname),"size":$(fsize),"w":$(imageInfo.width),"h":$(imageInfo.height),"hash":$(etag)}"#;
         let pp = types::PutPolicyBuilder::new(scope, 1451491200)
             .return_body(b.to_owned())
-            .build();
+            .build()
+            .unwrap();
         let result = pp.into_upload_token(&signer);
 
         assert_eq!(
@@ -44,4 +148,56 @@ mod tests {
             "MY_ACCESS_KEY:wQ4ofysef1R7IKnrziqtomqyDvI=:eyJzY29wZSI6Im15LWJ1Y2tldDpzdW5mbG93ZXIuanBnIiwiZGVhZGxpbmUiOjE0NTE0OTEyMDAsInJldHVybkJvZHkiOiJ7XCJuYW1lXCI6JChmbmFtZSksXCJzaXplXCI6JChmc2l6ZSksXCJ3XCI6JChpbWFnZUluZm8ud2lkdGgpLFwiaFwiOiQoaW1hZ2VJbmZvLmhlaWdodCksXCJoYXNoXCI6JChldGFnKX0ifQ=="
         );
     }
+
+    const TEST_TOKEN: &'static str = "MY_ACCESS_KEY:wQ4ofysef1R7IKnrziqtomqyDvI=:eyJzY29wZSI6Im15LWJ1Y2tldDpzdW5mbG93ZXIuanBnIiwiZGVhZGxpbmUiOjE0NTE0OTEyMDAsInJldHVybkJvZHkiOiJ7XCJuYW1lXCI6JChmbmFtZSksXCJzaXplXCI6JChmc2l6ZSksXCJ3XCI6JChpbWFnZUluZm8ud2lkdGgpLFwiaFwiOiQoaW1hZ2VJbmZvLmhlaWdodCksXCJoYXNoXCI6JChldGFnKX0ifQ==";
+
+    #[test]
+    fn test_parse_upload_token() {
+        let signer = sign::QiniuSigner::new("MY_ACCESS_KEY", "MY_SECRET_KEY");
+        let scope = types::PutScope::BucketKey("my-bucket".to_owned(), "sunflower.jpg".to_owned());
+        let b = r#"{"name":$(fname),"size":$(fsize),"w":$(imageInfo.width),"h":$(imageInfo.height),"hash":$(etag)}"#;
+        let pp = types::PutPolicyBuilder::new(scope, 1451491200)
+            .return_body(b.to_owned())
+            .build()
+            .unwrap();
+
+        let token = UploadToken::parse(TEST_TOKEN).unwrap();
+        assert_eq!(token.access_key(), "MY_ACCESS_KEY");
+        assert_eq!(token.signature(), "wQ4ofysef1R7IKnrziqtomqyDvI=");
+        assert_eq!(token.policy(), &pp);
+        assert_eq!(token.policy().scope(), "my-bucket:sunflower.jpg");
+        assert!(token.verify(&signer));
+        assert_eq!(token.expires_in_at(1451491100), 100);
+        assert_eq!(token.expires_in_at(1451491300), -100);
+        assert!(token.is_expired());
+
+        let other = sign::QiniuSigner::new("MY_ACCESS_KEY", "OTHER_SECRET_KEY");
+        assert!(!token.verify(&other));
+
+        // round trip
+        assert_eq!(token.into_policy().into_upload_token(&signer), TEST_TOKEN);
+    }
+
+    #[test]
+    fn test_parse_malformed_upload_token() {
+        assert!(UploadToken::parse("").is_err());
+        assert!(UploadToken::parse("a:b").is_err());
+        assert!(UploadToken::parse("a:b:!!!").is_err());
+        assert!(UploadToken::parse("a:b:bm90IGpzb24=").is_err());
+    }
+
+    #[test]
+    fn test_sign_download_url() {
+        let signer = sign::QiniuSigner::new("MY_ACCESS_KEY", "MY_SECRET_KEY");
+        let url = "http://78re52.com1.z0.glb.clouddn.com/resource/flower.jpg";
+
+        assert_eq!(
+            sign_download_url(&signer, url, 1451491200),
+            "http://78re52.com1.z0.glb.clouddn.com/resource/flower.jpg?e=1451491200&token=MY_ACCESS_KEY:438dd8pXocjYuF-6dTcKMtETB2g="
+        );
+        assert_eq!(
+            sign_download_url(&signer, &format!("{}?imageView2/1/w/100", url), 1451491200),
+            "http://78re52.com1.z0.glb.clouddn.com/resource/flower.jpg?imageView2/1/w/100&e=1451491200&token=MY_ACCESS_KEY:-Jcw7FJgQigSRczhJlmmSYo_UxY="
+        );
+    }
 }
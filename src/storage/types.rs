@@ -1,15 +1,83 @@
+use super::super::errors::*;
+
+
 /// Storage kind.
-#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+///
+/// Kinds are equal if their numeric constants are, so e.g. `Unknown(1)` is
+/// the same kind as `LowFrequency`.
+#[derive(Copy, Clone, Debug)]
 pub enum StorageKind {
     /// Conventional storage.
     Conventional,
     /// Low-frequency storage.
     LowFrequency,
+    /// Archive storage, objects must be restored before downloading.
+    Archive,
+    /// Deep archive storage, cheaper than archive storage but slower to
+    /// restore.
+    DeepArchive,
+    /// Intelligent tiering storage, moving objects between tiers by their
+    /// access frequency.
+    IntelligentTiering,
+    /// Storage kind unknown to this crate, with its numeric constant.
+    ///
+    /// [from_u64] and deserialization never return it for the constant of a
+    /// kind above.
+    ///
+    /// [from_u64]: #method.from_u64
+    Unknown(u64),
 }
 
 
 const STORAGE_KIND_CONVENTIONAL: u64 = 0;
 const STORAGE_KIND_LF: u64 = 1;
+const STORAGE_KIND_ARCHIVE: u64 = 2;
+const STORAGE_KIND_DEEP_ARCHIVE: u64 = 3;
+const STORAGE_KIND_INTELLIGENT_TIERING: u64 = 4;
+
+
+impl StorageKind {
+    /// Returns the storage kind of the numeric constant used in the APIs.
+    pub fn from_u64(x: u64) -> StorageKind {
+        match x {
+            STORAGE_KIND_CONVENTIONAL => StorageKind::Conventional,
+            STORAGE_KIND_LF => StorageKind::LowFrequency,
+            STORAGE_KIND_ARCHIVE => StorageKind::Archive,
+            STORAGE_KIND_DEEP_ARCHIVE => StorageKind::DeepArchive,
+            STORAGE_KIND_INTELLIGENT_TIERING => StorageKind::IntelligentTiering,
+            x => StorageKind::Unknown(x),
+        }
+    }
+
+    /// Returns the numeric constant of the storage kind used in the APIs.
+    pub fn to_u64(&self) -> u64 {
+        match self {
+            &StorageKind::Conventional => STORAGE_KIND_CONVENTIONAL,
+            &StorageKind::LowFrequency => STORAGE_KIND_LF,
+            &StorageKind::Archive => STORAGE_KIND_ARCHIVE,
+            &StorageKind::DeepArchive => STORAGE_KIND_DEEP_ARCHIVE,
+            &StorageKind::IntelligentTiering => STORAGE_KIND_INTELLIGENT_TIERING,
+            &StorageKind::Unknown(x) => x,
+        }
+    }
+}
+
+
+impl PartialEq for StorageKind {
+    fn eq(&self, other: &StorageKind) -> bool {
+        self.to_u64() == other.to_u64()
+    }
+}
+
+
+impl Eq for StorageKind {}
+
+
+impl ::std::hash::Hash for StorageKind {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        self.to_u64().hash(state)
+    }
+}
 
 
 impl ::serde::Serialize for StorageKind {
@@ -17,10 +85,7 @@ impl ::serde::Serialize for StorageKind {
     where
         S: ::serde::Serializer,
     {
-        serializer.serialize_u64(match self {
-            &StorageKind::Conventional => STORAGE_KIND_CONVENTIONAL,
-            &StorageKind::LowFrequency => STORAGE_KIND_LF,
-        })
+        serializer.serialize_u64(self.to_u64())
     }
 }
 
@@ -36,18 +101,14 @@ impl<'de> ::serde::Deserialize<'de> for StorageKind {
             type Value = StorageKind;
 
             fn expecting(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-                f.write_str("storage kind constant (0 or 1)")
+                f.write_str("storage kind constant")
             }
 
-            fn visit_u64<E>(self, value: u64) -> Result<StorageKind, E>
+            fn visit_u64<E>(self, value: u64) -> ::std::result::Result<StorageKind, E>
             where
                 E: ::serde::de::Error,
             {
-                match value {
-                    STORAGE_KIND_CONVENTIONAL => Ok(StorageKind::Conventional),
-                    STORAGE_KIND_LF => Ok(StorageKind::LowFrequency),
-                    _ => Err(E::custom(format!("unknown StorageKind: {}", value))),
-                }
+                Ok(StorageKind::from_u64(value))
             }
         }
 
@@ -157,6 +218,33 @@ pub struct PutPolicy {
     mime_limit: Option<String>,
     #[serde(rename = "fileType", skip_serializing_if = "Option::is_none")]
     file_type: Option<StorageKind>,
+    #[serde(rename = "forceSaveKey", skip_serializing_if = "Option::is_none")]
+    force_save_key: Option<bool>,
+    #[serde(rename = "deleteAfterDays", skip_serializing_if = "Option::is_none")]
+    delete_after_days: Option<u32>,
+    #[serde(rename = "callbackFetchKey", skip_serializing_if = "Option::is_none")]
+    callback_fetch_key: Option<isize>,
+    #[serde(rename = "trafficLimit", skip_serializing_if = "Option::is_none")]
+    traffic_limit: Option<u64>,
+}
+
+
+/// Range of allowed single-link traffic limits, in bits per second.
+const TRAFFIC_LIMIT_MIN: u64 = 819200;
+const TRAFFIC_LIMIT_MAX: u64 = 838860800;
+
+
+impl PutPolicy {
+    /// Returns the scope of the policy, i.e. the bucket optionally followed by
+    /// `:` and the key or key prefix.
+    pub fn scope(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Returns the Unix timestamp after which uploads are rejected.
+    pub fn deadline(&self) -> u32 {
+        self.unix_timestamp
+    }
 }
 
 
@@ -171,6 +259,17 @@ fn to_option_int(x: bool) -> Option<isize> {
 }
 
 
+/// Convert the size in bytes to the type used in the policy, saturating at
+/// `i64::MAX` which no upload can reach anyway.
+fn to_policy_size(size: u64) -> i64 {
+    if size > i64::max_value() as u64 {
+        i64::max_value()
+    } else {
+        size as i64
+    }
+}
+
+
 impl PutPolicyBuilder {
     /// Begin constructing a put policy.
     pub fn new(scope: PutScope, deadline: u32) -> Self {
@@ -190,9 +289,48 @@ impl PutPolicyBuilder {
         }
     }
 
-    /// Return the constructed put policy.
-    pub fn build(self) -> PutPolicy {
-        self.inner
+    /// Return the constructed put policy, or an error if the settings
+    /// contradict each other.
+    pub fn build(self) -> Result<PutPolicy> {
+        {
+            let p = &self.inner;
+
+            if let (Some(min), Some(limit)) = (p.file_size_min, p.file_size_limit) {
+                if min > limit {
+                    bail!("fsizeMin {} is larger than fsizeLimit {}", min, limit);
+                }
+            }
+            if p.force_save_key.is_some() && p.save_key.is_none() {
+                bail!("forceSaveKey requires saveKey");
+            }
+            if p.request_url_for_app_server.is_none() {
+                if p.request_host_for_app_server.is_some() || p.request_body_for_app_server.is_some() ||
+                    p.request_body_type_for_app_server.is_some() ||
+                    p.callback_fetch_key.is_some()
+                {
+                    bail!("callback settings require callbackUrl");
+                }
+            } else if p.request_body_for_app_server.is_none() {
+                bail!("callbackUrl requires callbackBody");
+            }
+            if p.persistent_ops_cmds.is_none() &&
+                (p.persistent_notify_url.is_some() || p.persistent_pipeline.is_some())
+            {
+                bail!("persistent processing settings require persistentOps");
+            }
+            if let Some(limit) = p.traffic_limit {
+                if limit < TRAFFIC_LIMIT_MIN || limit > TRAFFIC_LIMIT_MAX {
+                    bail!(
+                        "trafficLimit {} is out of range [{}, {}]",
+                        limit,
+                        TRAFFIC_LIMIT_MIN,
+                        TRAFFIC_LIMIT_MAX
+                    );
+                }
+            }
+        }
+
+        Ok(self.inner)
     }
 
     /// Set the end user ID for the upload.
@@ -248,7 +386,106 @@ impl PutPolicyBuilder {
         self
     }
 
-    // TODO: remaining fields
+    /// Set the [persistent data processing][pfop] commands to run on the
+    /// upload.
+    ///
+    /// [pfop]: https://developer.qiniu.com/dora/manual/3686/pfop-directions-for-use
+    pub fn persistent_ops(mut self, cmds: Vec<String>) -> Self {
+        self.inner.persistent_ops_cmds = Some(cmds.join(";"));
+        self
+    }
+
+    /// Set the URL notified of persistent data processing results.
+    pub fn persistent_notify_url(mut self, url: String) -> Self {
+        self.inner.persistent_notify_url = Some(url);
+        self
+    }
+
+    /// Set the pipeline persistent data processing is queued on.
+    pub fn persistent_pipeline(mut self, pipeline: String) -> Self {
+        self.inner.persistent_pipeline = Some(pipeline);
+        self
+    }
+
+    /// Set the key template used when the uploader does not specify a key.
+    pub fn save_key(mut self, template: String) -> Self {
+        self.inner.save_key = Some(template);
+        self
+    }
+
+    /// Use the `saveKey` template even if the uploader specifies a key.
+    pub fn force_save_key(mut self, force: bool) -> Self {
+        self.inner.force_save_key = if force { Some(true) } else { None };
+        self
+    }
+
+    /// Reject uploads smaller than the given size in bytes.
+    ///
+    /// Sizes beyond `i64::MAX` are clamped to it.
+    pub fn file_size_min(mut self, size: u64) -> Self {
+        self.inner.file_size_min = Some(to_policy_size(size));
+        self
+    }
+
+    /// Reject uploads larger than the given size in bytes.
+    ///
+    /// Sizes beyond `i64::MAX` are clamped to it.
+    pub fn file_size_limit(mut self, size: u64) -> Self {
+        self.inner.file_size_limit = Some(to_policy_size(size));
+        self
+    }
+
+    /// Only accept uploads with sizes in the given inclusive range.
+    pub fn file_size_range(self, min: u64, limit: u64) -> Self {
+        self.file_size_min(min).file_size_limit(limit)
+    }
+
+    /// Detect the MIME type of the upload on the server, ignoring the type
+    /// given by the uploader.
+    pub fn detect_mime(mut self, detect: bool) -> Self {
+        self.inner.auto_detect_mime_type = to_option_int(detect);
+        self
+    }
+
+    /// Only accept uploads with MIME types matching any of the patterns,
+    /// e.g. `image/*`.
+    pub fn mime_limit(mut self, patterns: Vec<String>) -> Self {
+        self.inner.mime_limit = Some(patterns.join(";"));
+        self
+    }
+
+    /// Reject uploads with MIME types matching any of the patterns.
+    pub fn mime_exclude(mut self, patterns: Vec<String>) -> Self {
+        let mut tmp = String::from("!");
+        tmp.push_str(&patterns.join(";"));
+        self.inner.mime_limit = Some(tmp);
+        self
+    }
+
+    /// Set the storage kind of the upload.
+    pub fn file_type(mut self, kind: StorageKind) -> Self {
+        self.inner.file_type = Some(kind);
+        self
+    }
+
+    /// Delete the upload after the given number of days.
+    pub fn delete_after_days(mut self, days: u32) -> Self {
+        self.inner.delete_after_days = if days > 0 { Some(days) } else { None };
+        self
+    }
+
+    /// Let the app server decide the key of the upload in the callback
+    /// response.
+    pub fn callback_fetch_key(mut self, fetch: bool) -> Self {
+        self.inner.callback_fetch_key = to_option_int(fetch);
+        self
+    }
+
+    /// Limit the upload speed, in bits per second.
+    pub fn traffic_limit(mut self, bits_per_sec: u64) -> Self {
+        self.inner.traffic_limit = Some(bits_per_sec);
+        self
+    }
 }
 
 
@@ -264,3 +501,105 @@ pub struct ImageInfo {
     pub height: usize,
     pub color_model: String,
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    fn builder() -> PutPolicyBuilder {
+        PutPolicyBuilder::new(PutScope::Bucket("my-bucket".to_owned()), 1451491200)
+    }
+
+    #[test]
+    fn test_storage_kind() {
+        for &(kind, x) in &[
+            (StorageKind::Conventional, 0),
+            (StorageKind::LowFrequency, 1),
+            (StorageKind::Archive, 2),
+            (StorageKind::DeepArchive, 3),
+            (StorageKind::IntelligentTiering, 4),
+            (StorageKind::Unknown(42), 42),
+        ]
+        {
+            assert_eq!(kind.to_u64(), x);
+            assert_eq!(StorageKind::from_u64(x), kind);
+            assert_eq!(::serde_json::to_string(&kind).unwrap(), x.to_string());
+            assert_eq!(::serde_json::from_str::<StorageKind>(&x.to_string()).unwrap(), kind);
+        }
+
+        assert!(::serde_json::from_str::<StorageKind>("-1").is_err());
+
+        // unknown kinds with known constants are the known kinds
+        assert_eq!(StorageKind::Unknown(1), StorageKind::LowFrequency);
+        assert_ne!(StorageKind::Unknown(5), StorageKind::Unknown(6));
+        let kinds = [StorageKind::Unknown(2), StorageKind::Archive]
+            .iter()
+            .cloned()
+            .collect::<::std::collections::HashSet<_>>();
+        assert_eq!(kinds.len(), 1);
+    }
+
+    #[test]
+    fn test_put_policy_build() {
+        let pp = builder()
+            .save_key("$(etag)$(ext)".to_owned())
+            .force_save_key(true)
+            .file_size_range(1, 1024)
+            .mime_limit(vec!["image/*".to_owned(), "video/*".to_owned()])
+            .file_type(StorageKind::LowFrequency)
+            .delete_after_days(30)
+            .traffic_limit(TRAFFIC_LIMIT_MIN)
+            .build()
+            .unwrap();
+        let json = ::serde_json::to_value(&pp).unwrap();
+
+        assert_eq!(json["saveKey"], "$(etag)$(ext)");
+        assert_eq!(json["forceSaveKey"], true);
+        assert_eq!(json["fsizeMin"], 1);
+        assert_eq!(json["fsizeLimit"], 1024);
+        assert_eq!(json["mimeLimit"], "image/*;video/*");
+        assert_eq!(json["fileType"], 1);
+        assert_eq!(json["deleteAfterDays"], 30);
+        assert_eq!(json["trafficLimit"], TRAFFIC_LIMIT_MIN);
+    }
+
+    #[test]
+    fn test_put_policy_file_size_saturates() {
+        let pp = builder()
+            .file_size_range(u64::max_value() - 1, u64::max_value())
+            .build()
+            .unwrap();
+        let json = ::serde_json::to_value(&pp).unwrap();
+
+        assert_eq!(json["fsizeMin"], i64::max_value());
+        assert_eq!(json["fsizeLimit"], i64::max_value());
+        assert_eq!(to_policy_size(i64::max_value() as u64), i64::max_value());
+        assert_eq!(to_policy_size(i64::max_value() as u64 + 1), i64::max_value());
+    }
+
+    #[test]
+    fn test_put_policy_build_contradictions() {
+        assert!(builder().file_size_range(1024, 1).build().is_err());
+        assert!(builder().force_save_key(true).build().is_err());
+        assert!(builder().callback_body("a=b".to_owned()).build().is_err());
+        assert!(builder().callback_url("http://x".to_owned()).build().is_err());
+        assert!(builder().callback_fetch_key(true).build().is_err());
+        assert!(
+            builder()
+                .persistent_notify_url("http://x".to_owned())
+                .build()
+                .is_err()
+        );
+        assert!(builder().traffic_limit(1).build().is_err());
+        assert!(
+            builder()
+                .callback_url("http://x".to_owned())
+                .callback_body("a=b".to_owned())
+                .callback_fetch_key(true)
+                .build()
+                .is_ok()
+        );
+    }
+}
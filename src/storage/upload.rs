@@ -0,0 +1,140 @@
+//! [Form uploads][form-upload].
+//!
+//! [form-upload]: https://developer.qiniu.com/kodo/api/1312/upload
+
+use bytes;
+use crc;
+use serde;
+
+#[cfg(feature = "async-api")]
+use futures::prelude::*;
+
+use super::super::errors::*;
+use super::super::multipart;
+use super::super::provider;
+use super::super::request;
+use super::super::reqwest_compat as reqwest;
+use super::client::{self, QiniuStorageClient};
+
+
+/// Optional parameters shared by all kinds of uploads.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct UploadExtra {
+    /// Original file name of the upload, available to the `fname` magic
+    /// variable.
+    pub file_name: Option<String>,
+    /// MIME type of the upload. Detected by the server if not given.
+    pub mime_type: Option<String>,
+    /// [Custom variables][xvar] for use in `returnBody` and `callbackBody`.
+    ///
+    /// The `x:` prefix is automatically prepended if missing.
+    ///
+    /// [xvar]: https://developer.qiniu.com/kodo/manual/1235/vars#xvar
+    pub custom_vars: Vec<(String, String)>,
+}
+
+
+impl UploadExtra {
+    pub(crate) fn custom_var_pairs<'a>(&'a self) -> impl Iterator<Item = (String, &'a str)> + 'a {
+        self.custom_vars.iter().map(|&(ref k, ref v)| {
+            let k = if k.starts_with("x:") {
+                k.clone()
+            } else {
+                format!("x:{}", k)
+            };
+            (k, v.as_str())
+        })
+    }
+}
+
+
+/// Response of uploads without a custom `returnBody`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadResponse {
+    pub key: Option<String>,
+    pub hash: String,
+}
+
+
+impl<'a> QiniuStorageClient<'a> {
+    fn req_upload_form(
+        &self,
+        token: &str,
+        key: Option<&str>,
+        data: bytes::Bytes,
+        extra: &UploadExtra,
+    ) -> Result<request::QiniuRequest> {
+        let mut form = multipart::MultipartForm::new()?;
+
+        form.add_text("token", token);
+        if let Some(key) = key {
+            form.add_text("key", key);
+        }
+        for (k, v) in extra.custom_var_pairs() {
+            form.add_text(&k, v);
+        }
+        form.add_text("crc32", &format!("{}", crc::crc32::checksum_ieee(&data)));
+        form.add_file(
+            "file",
+            extra.file_name.as_ref().map_or("file", |x| x.as_str()),
+            extra.mime_type.as_ref().map_or(
+                "application/octet-stream",
+                |x| x.as_str(),
+            ),
+            &data,
+        );
+
+        let (content_type, body) = form.finish();
+
+        Ok(
+            request::QiniuRequest::new(reqwest::Method::Post, self.hosts().up(), Some(body))?
+                .with_content_type(content_type)
+                .with_auth(request::Authorization::None)
+                .with_endpoints(self.hosts().endpoints(provider::Service::Up)),
+        )
+    }
+
+    /// Upload the data in a single multipart/form-data request.
+    ///
+    /// The response is the decoded `returnBody` of the put policy, use
+    /// [UploadResponse] if `returnBody` is not customized, or
+    /// `serde_json::Value` for arbitrary bodies.
+    ///
+    /// [UploadResponse]: ./struct.UploadResponse.html
+    #[cfg(feature = "async-api")]
+    pub fn upload_form<T>(
+        &self,
+        token: &str,
+        key: Option<&str>,
+        data: bytes::Bytes,
+        extra: &UploadExtra,
+    ) -> impl Future<Item = T, Error = Error>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let req = self.req_upload_form(token, key, data, extra);
+        client::execute_json(self.provider, req)
+    }
+
+    /// Upload the data in a single multipart/form-data request.
+    ///
+    /// The response is the decoded `returnBody` of the put policy, use
+    /// [UploadResponse] if `returnBody` is not customized, or
+    /// `serde_json::Value` for arbitrary bodies.
+    ///
+    /// [UploadResponse]: ./struct.UploadResponse.html
+    #[cfg(feature = "sync-api")]
+    pub fn upload_form<T>(
+        &self,
+        token: &str,
+        key: Option<&str>,
+        data: bytes::Bytes,
+        extra: &UploadExtra,
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let req = self.req_upload_form(token, key, data, extra);
+        client::execute_json(self.provider, req)
+    }
+}